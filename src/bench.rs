@@ -0,0 +1,210 @@
+use crate::compile::{compile, compile_tracked};
+use crate::{execute_with_limit, try_lex, try_parse, Object};
+
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{Duration, Instant};
+
+// Name, program and input. Copies of the codegolf solutions in codegolf.rs, then cases
+// that stress one part of the interpreter each.
+pub const CORPUS: &[(&str, &str, &str)] = &[
+    ("make_2014", "ttsmzyhhyhh", "0"),
+    ("primality_map", "stlfsmqstlxlipzbihlqtnwttmh", "29"),
+    ("primality_fixed_point", "sttlfyipbihlqxtmh", "29"),
+    ("primality_power", "stlfyipbihlqtxtmh", "29"),
+    ("primality_inverse", "ihpzbpmptmbq", "29"),
+    ("primality_builtin", "ip", "29"),
+    ("fibonacci_fixed_point", "ihhhzxbthzqbshihqbzbhhzhm", "9"),
+    ("fibonacci_repeat", "ihhzrbshbzbhhzhm", "9"),
+    ("fibonacci_short", "ihsrbshnbms", "9"),
+    ("nesting", "htnrbhqbht", "[5, -1]"),
+    ("reshaped", "ttfecbxitxt", "[0, 1, 2, 3, 4, 0, 1, 2]"),
+    ("anagram", "emo", "[[0, 1, 2, 3], [2, 3, 1, 0]]"),
+    ("sqrt_combine", "smeboqcbmqpbhhqr", "10"),
+    ("sqrt_filter", "lfeboqabmqpbhhq", "10"),
+    (
+        "group_by_displacement",
+        "mmihgqhhonsqcbmnhzlz",
+        "[5, 6, 8, 3, 9, 4, 2, 1, 7]",
+    ),
+    (
+        "even_subarrays",
+        "smsihlsqfzsmxitxt",
+        "[0, 6, 6, 5, 3, 3, 8]",
+    ),
+    (
+        "distinct_distances",
+        "iholfebdzzmqsmpmbqmqmscumnbkqfqseboqqpbqzypcmb",
+        "2",
+    ),
+    ("permutations", "c", "8"),
+    ("partitions", "d", "14"),
+    (
+        "power_set",
+        "y",
+        "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13]",
+    ),
+    ("order", "oqnq", "20000"),
+    ("reverse_lookup", "vqpq", "[2, 3, 5, 7, 11]"),
+    ("factorize", "p", "1000000016000000063"),
+    ("big_prime", "ip", "170141183460469231731687303715884105727"),
+];
+
+// Sizes stop being counted past this, since shared lists can be exponentially large.
+const MAX_SIZE: u64 = 1 << 32;
+
+thread_local! {
+    // The largest object seen since tracking started, if tracking.
+    static PEAK: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Called by tracked code on each intermediate object.
+pub fn note(obj: &Object) {
+    if PEAK.with(|p| p.get()).is_some() {
+        let size = size(obj);
+        PEAK.with(|p| p.set(p.get().map(|peak| peak.max(size))));
+    }
+}
+
+// Ints, lists and errors each count as one, and lists add up their made elements.
+fn size(obj: &Object) -> u64 {
+    let mut total = 0;
+    add_size(obj, &mut total);
+    total.min(MAX_SIZE)
+}
+
+fn add_size(obj: &Object, total: &mut u64) {
+    *total += 1;
+    if *total >= MAX_SIZE {
+        return;
+    }
+    if let Object::List(l) = obj {
+        l.for_each_made(|elem| add_size(elem, total));
+    }
+}
+
+pub struct Measurement {
+    pub name: String,
+    pub median: Duration,
+    pub steps: u64,
+    pub peak: u64,
+}
+
+// Times repetitions runs of program, then runs it once more untimed to find the steps
+// and peak size, since tracking the peak walks every intermediate object.
+pub fn measure(name: &str, program: &str, input: &str, repetitions: usize) -> Measurement {
+    let func = try_lex(program)
+        .and_then(try_parse)
+        .expect("Corpus programs parse");
+    let code = compile(&func);
+    let input = Object::from_str(input);
+    let mut times = vec![];
+    for _ in 0..repetitions.max(1) {
+        let start = Instant::now();
+        execute_with_limit(&code, input.clone(), None);
+        times.push(start.elapsed());
+    }
+    PEAK.with(|p| p.set(Some(0)));
+    let (output, steps) = execute_with_limit(&compile_tracked(&func), input, None);
+    // The output is only fully made once the run is over.
+    note(&output);
+    let peak = PEAK.with(|p| p.replace(None)).expect("Tracking");
+    times.sort();
+    Measurement {
+        name: name.to_string(),
+        median: times[times.len() / 2],
+        steps,
+        peak,
+    }
+}
+
+// Runs every program in the corpus repetitions times.
+pub fn run(repetitions: usize) -> Vec<Measurement> {
+    CORPUS
+        .iter()
+        .map(|(name, program, input)| measure(name, program, input, repetitions))
+        .collect()
+}
+
+// One line per program: name, median time in microseconds, steps and peak size, tab separated.
+pub fn save(measurements: &[Measurement], path: &str) -> std::io::Result<()> {
+    let mut out = String::new();
+    for m in measurements {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            m.name,
+            m.median.as_micros(),
+            m.steps,
+            m.peak
+        )
+        .expect("Writing to a string");
+    }
+    fs::write(path, out)
+}
+
+pub fn load(path: &str) -> std::io::Result<Vec<Measurement>> {
+    let contents = fs::read_to_string(path)?;
+    let invalid = |line: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Bad benchmark line: {}", line),
+        )
+    };
+    contents
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if let [name, micros, steps, peak] = fields[..] {
+                Ok(Measurement {
+                    name: name.to_string(),
+                    median: Duration::from_micros(micros.parse().map_err(|_| invalid(line))?),
+                    steps: steps.parse().map_err(|_| invalid(line))?,
+                    peak: peak.parse().map_err(|_| invalid(line))?,
+                })
+            } else {
+                Err(invalid(line))
+            }
+        })
+        .collect()
+}
+
+// A table of the measurements. Given an earlier run, also shows how each program's
+// time changed, and marks programs whose steps or peak size changed.
+pub fn report(measurements: &[Measurement], baseline: Option<&[Measurement]>) -> String {
+    let mut out = format!(
+        "{:<24}{:>12}{:>12}{:>12}",
+        "program", "median us", "steps", "peak"
+    );
+    if baseline.is_some() {
+        write!(out, "{:>10}", "time").expect("Writing to a string");
+    }
+    for m in measurements {
+        write!(
+            out,
+            "\n{:<24}{:>12}{:>12}{:>12}",
+            m.name,
+            m.median.as_micros(),
+            m.steps,
+            m.peak
+        )
+        .expect("Writing to a string");
+        let before = baseline.and_then(|b| b.iter().find(|old| old.name == m.name));
+        match (baseline, before) {
+            (None, _) => {}
+            (Some(_), None) => out.push_str("       new"),
+            (Some(_), Some(old)) => {
+                let ratio = m.median.as_secs_f64() / old.median.as_secs_f64().max(1e-6);
+                write!(out, "{:>9.0}%", (ratio - 1.0) * 100.0).expect("Writing to a string");
+                if old.steps != m.steps {
+                    write!(out, "  steps were {}", old.steps).expect("Writing to a string");
+                }
+                if old.peak != m.peak {
+                    write!(out, "  peak was {}", old.peak).expect("Writing to a string");
+                }
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,48 @@
+use crate::{out_of_steps, Callee, Func, HigherFunc, Object};
+
+// Marks every inverse in func with the position of its i in program, so that it's checked.
+// The tree holds its atoms in the same order as the program does.
+pub fn mark_inverses(func: Func, program: &str) -> Func {
+    let mut positions = program
+        .char_indices()
+        .filter(|(_, c)| *c == 'i')
+        .map(|(index, _)| index);
+    mark(func, &mut positions)
+}
+
+fn mark(func: Func, positions: &mut impl Iterator<Item = usize>) -> Func {
+    match func {
+        Func::Higher(HigherFunc::Inverse, inner) => {
+            let position = positions.next().expect("An i for each inverse");
+            Func::Higher(
+                HigherFunc::CheckedInverse(position),
+                Box::new(mark(*inner, positions)),
+            )
+        }
+        Func::Higher(higher_func, inner) => {
+            Func::Higher(higher_func, Box::new(mark(*inner, positions)))
+        }
+        Func::Double(double_func, func1, func2) => {
+            let func1 = mark(*func1, positions);
+            let func2 = mark(*func2, positions);
+            Func::Double(double_func, Box::new(func1), Box::new(func2))
+        }
+        Func::Bound(funcs) => Func::Bound(funcs.into_iter().map(|f| mark(f, positions)).collect()),
+        Func::Basic(_) | Func::Literal(_) => func,
+    }
+}
+
+// If func doesn't map result back to arg, what it maps result to instead.
+// Errors, and running out of steps, don't count.
+pub fn mismatch<F: Callee>(func: &F, arg: &Object, result: &Object) -> Option<Object> {
+    if matches!(arg, Object::Error(_)) || matches!(result, Object::Error(_)) {
+        return None;
+    }
+    let back = func.execute(result.clone());
+    back.force();
+    if out_of_steps() || &back == arg {
+        None
+    } else {
+        Some(back)
+    }
+}
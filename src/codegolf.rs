@@ -13,10 +13,12 @@ fn make_2014() {
 #[test]
 fn primality() {
     // https://codegolf.stackexchange.com/questions/57617
+    // The first three took the remainder from ip on a pair, which now divides exactly,
+    // so they check for a whole quotient instead.
     let programs = vec![
-        "stlfsmqihpzbihlqtnwttmh",
-        "sttlfsihpzbihlqxtmh",
-        "stlfspipbihlqtxtmh",
+        "stlfsmqstlxlipzbihlqtnwttmh",
+        "sttlfyipbihlqxtmh",
+        "stlfyipbihlqtxtmh",
         "ihpzbpmptmbq",
         "ip",
     ];
@@ -57,16 +59,25 @@ fn nesting() {
     let program = "htnrbhqbht";
     let output = run_prog(program, list_int_to_obj(vec![5, -1]));
     let n1 = int_to_obj(-1);
-    let desired_output = List(vec![
-        n1.clone(),
-        List(vec![
+    let desired_output = List(
+        vec![
             n1.clone(),
-            List(vec![
-                n1.clone(),
-                List(vec![n1.clone(), List(vec![n1.clone()])]),
-            ]),
-        ]),
-    ]);
+            List(
+                vec![
+                    n1.clone(),
+                    List(
+                        vec![
+                            n1.clone(),
+                            List(vec![n1.clone(), List(vec![n1.clone()].into())].into()),
+                        ]
+                        .into(),
+                    ),
+                ]
+                .into(),
+            ),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -75,7 +86,7 @@ fn reshaped() {
     // https://codegolf.stackexchange.com/questions/239788
     let program = "ttfecbxitxt";
     let output = run_prog(program, list_int_to_obj(vec![0, 1, 2, 3, 4, 0, 1, 2]));
-    let desired_output = List(vec![lli_to_obj(vec![vec![], vec![]])]);
+    let desired_output = List(vec![lli_to_obj(vec![vec![], vec![]])].into());
     assert_eq!(desired_output, output);
 }
 
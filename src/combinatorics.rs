@@ -0,0 +1,131 @@
+use crate::number::Num;
+use crate::seq::Seq;
+use crate::{take_step, take_steps, too_long, Object, OUT_OF_STEPS};
+
+use num_traits::cast::ToPrimitive;
+use num_traits::{One, Zero};
+
+use std::sync::Arc;
+
+// The number of ordered selections of k of n things.
+fn selections(n: usize, k: usize) -> Num {
+    (n - k + 1..=n).fold(One::one(), |total: Num, i| total * Num::from(i))
+}
+
+// The ordered selection of k of the indices 0..n at rank, in lexicographic order.
+fn unrank_selection(n: usize, k: usize, rank: &Num) -> Vec<usize> {
+    let mut rank = rank.clone();
+    let mut unused: Vec<usize> = (0..n).collect();
+    let mut chosen = vec![];
+    if k == 0 {
+        return chosen;
+    }
+    // How many selections share each choice of the next element.
+    let mut block = selections(n - 1, k - 1);
+    for i in 0..k {
+        let choice = (&rank / &block).to_usize().expect("Rank in bounds");
+        rank %= &block;
+        chosen.push(unused.remove(choice));
+        if i + 1 < k {
+            block /= Num::from(n - i - 1);
+        }
+    }
+    chosen
+}
+
+// The lowest count binary digits of rank, lowest first.
+fn low_bits(rank: &Num, count: usize) -> Vec<bool> {
+    let mut rank = rank.clone();
+    let mut bits = vec![];
+    for _ in 0..count {
+        bits.push(!(&rank % 2).is_zero());
+        rank /= 2;
+    }
+    bits
+}
+
+// The elements of base at indices. Making each costs a step.
+fn pick(base: &Seq, indices: &[usize]) -> Object {
+    take_steps(indices.len() as u64);
+    Object::List(
+        indices
+            .iter()
+            .map(|&index| base.nth(&Num::from(index)))
+            .collect(),
+    )
+}
+
+// Every permutation of base, in lexicographic order of positions.
+// Like the others here, None if base is too long to hold in memory.
+pub fn permutations(base: Seq) -> Option<Seq> {
+    let n = base.len()?;
+    let unrank = move |rank: &Num| pick(&base, &unrank_selection(n, n, rank));
+    Some(Seq::indexed(selections(n, n), Arc::new(unrank)))
+}
+
+// Every ordered selection of distinct elements of base, shortest first,
+// then in lexicographic order of positions. An error if base is too long,
+// or if counting them runs out of steps.
+pub fn arrangements(base: Seq) -> Result<Seq, Object> {
+    let n = base.len().ok_or_else(too_long)?;
+    let mut len: Num = Zero::zero();
+    for k in 0..=n {
+        // Each term is a big product, so it costs a step.
+        if !take_step() {
+            return Err(Object::Error(OUT_OF_STEPS.to_string()));
+        }
+        len += selections(n, k);
+    }
+    let unrank = move |rank: &Num| {
+        let mut rank = rank.clone();
+        for k in 0..=n {
+            let count = selections(n, k);
+            if rank < count {
+                return pick(&base, &unrank_selection(n, k, &rank));
+            }
+            rank -= count;
+        }
+        unreachable!("Rank in bounds")
+    };
+    Ok(Seq::indexed(len, Arc::new(unrank)))
+}
+
+// Every way to split base into consecutive nonempty runs. Split points between
+// elements count up in binary, with the first split point as the lowest bit.
+pub fn partitions(base: Seq) -> Option<Seq> {
+    let n = base.len()?;
+    if n == 0 {
+        return Some(Seq::default());
+    }
+    let unrank = move |rank: &Num| {
+        take_steps(n as u64);
+        let mut parts = vec![vec![base.nth(&Zero::zero())]];
+        for (pos, split) in low_bits(rank, n - 1).into_iter().enumerate() {
+            if split {
+                parts.push(vec![]);
+            }
+            let last = parts.len() - 1;
+            parts[last].push(base.nth(&Num::from(pos + 1)));
+        }
+        Object::List(
+            parts
+                .into_iter()
+                .map(|part| Object::List(part.into()))
+                .collect(),
+        )
+    };
+    let exponent = u32::try_from(n - 1).ok()?;
+    Some(Seq::indexed(Num::from(2).pow(exponent), Arc::new(unrank)))
+}
+
+// Every subset of base, counting up in binary with the first element as the lowest bit.
+pub fn subsets(base: Seq) -> Option<Seq> {
+    let n = base.len()?;
+    let exponent = u32::try_from(n).ok()?;
+    let unrank = move |rank: &Num| {
+        let mask = low_bits(rank, n);
+        let chosen: Vec<usize> = (0..n).filter(|&index| mask[index]).collect();
+        pick(&base, &chosen)
+    };
+    Some(Seq::indexed(Num::from(2).pow(exponent), Arc::new(unrank)))
+}
@@ -0,0 +1,217 @@
+use crate::bench;
+use crate::neighbours::quietly;
+use crate::{
+    execute_with_limit, out_of_steps, take_hash_steps, take_step, BasicFunc, Callee, DoubleFunc,
+    Func, HigherFunc, Num, Object, OUT_OF_STEPS,
+};
+
+use std::collections::HashMap;
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// A Func compiled to a flat list of instructions, with nested sequences spliced in
+// and every atom resolved ahead of time. Like a sequence, it runs from last to first.
+#[derive(Clone)]
+pub struct Code {
+    ops: Arc<[Op]>,
+    // Inverses are run by the tree-walker on the original func.
+    source: Arc<Func>,
+    memo: Option<Arc<Memo>>,
+    // Whether each intermediate object is passed to the bench to track the peak size.
+    track: bool,
+}
+
+// Remembers the results of one function, by input. Every function is pure,
+// so a result can be reused whenever the same input comes around again.
+struct Memo {
+    capacity: usize,
+    table: Mutex<HashMap<Object, Object>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+enum Op {
+    Basic(BasicFunc),
+    Higher(HigherFunc, Code),
+    Double(DoubleFunc, Code, Code),
+    Literal(Object),
+}
+
+pub fn compile(func: &Func) -> Code {
+    build(func, None, false)
+}
+
+// Like compile, but every function passed to a higher-order function
+// remembers up to capacity of its results.
+pub fn compile_memoized(func: &Func, capacity: usize) -> Code {
+    build(func, Some(capacity), false)
+}
+
+// Like compile, but notes every intermediate object for the bench,
+// which would slow down every other run.
+pub fn compile_tracked(func: &Func) -> Code {
+    build(func, None, true)
+}
+
+fn build(func: &Func, capacity: Option<usize>, track: bool) -> Code {
+    let mut ops = vec![];
+    emit(func, &mut ops, capacity, track);
+    Code {
+        ops: ops.into(),
+        source: Arc::new(func.clone()),
+        memo: None,
+        track,
+    }
+}
+
+fn build_inner(func: &Func, capacity: Option<usize>, track: bool) -> Code {
+    let mut code = build(func, capacity, track);
+    code.memo = capacity.map(|capacity| {
+        Arc::new(Memo {
+            capacity,
+            table: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    });
+    code
+}
+
+fn emit(func: &Func, ops: &mut Vec<Op>, capacity: Option<usize>, track: bool) {
+    match func {
+        Func::Basic(basic) => ops.push(Op::Basic(basic.clone())),
+        Func::Higher(higher_func, inner) => ops.push(Op::Higher(
+            higher_func.clone(),
+            build_inner(inner, capacity, track),
+        )),
+        Func::Double(double_func, func1, func2) => ops.push(Op::Double(
+            double_func.clone(),
+            build_inner(func1, capacity, track),
+            build_inner(func2, capacity, track),
+        )),
+        Func::Bound(funcs) => funcs
+            .iter()
+            .for_each(|func| emit(func, ops, capacity, track)),
+        Func::Literal(object) => ops.push(Op::Literal(object.clone())),
+    }
+}
+
+impl Code {
+    pub fn run(&self, arg: Object) -> Object {
+        let memo = match &self.memo {
+            Some(memo) => memo,
+            None => return self.run_ops(arg),
+        };
+        if !take_hash_steps(&arg) {
+            return Object::Error(OUT_OF_STEPS.to_string());
+        }
+        let found = memo.table.lock().expect("Not poisoned").get(&arg).cloned();
+        if let Some(result) = found {
+            memo.hits.fetch_add(1, Ordering::Relaxed);
+            return result;
+        }
+        memo.misses.fetch_add(1, Ordering::Relaxed);
+        let key = arg.clone();
+        let result = self.run_ops(arg);
+        // A run cut short by the step limit isn't the real result.
+        if !out_of_steps() {
+            let mut table = memo.table.lock().expect("Not poisoned");
+            // When full, start over rather than tracking which results are stale.
+            if table.len() >= memo.capacity {
+                table.clear();
+            }
+            table.insert(key, result.clone());
+        }
+        result
+    }
+    // The VM loop: one step per instruction, no matter how deeply it was nested.
+    fn run_ops(&self, arg: Object) -> Object {
+        let mut working_obj = arg;
+        for op in self.ops.iter().rev() {
+            if !take_step() {
+                return Object::Error(OUT_OF_STEPS.to_string());
+            }
+            working_obj = match op {
+                Op::Basic(basic) => basic.execute(working_obj),
+                Op::Higher(higher_func, inner) => higher_func.execute(inner, working_obj),
+                Op::Double(double_func, inner1, inner2) => {
+                    double_func.execute(inner1, inner2, working_obj)
+                }
+                Op::Literal(object) => object.clone(),
+            };
+            if self.track {
+                bench::note(&working_obj);
+            }
+        }
+        // Like the tree-walker, whatever was made from a list cut short is wrong.
+        if out_of_steps() {
+            Object::Error(OUT_OF_STEPS.to_string())
+        } else {
+            working_obj
+        }
+    }
+    // Hits and misses of every memo in this code, added up.
+    pub fn memo_stats(&self) -> MemoStats {
+        let mut stats = MemoStats::default();
+        if let Some(memo) = &self.memo {
+            stats.hits += memo.hits.load(Ordering::Relaxed);
+            stats.misses += memo.misses.load(Ordering::Relaxed);
+        }
+        for op in self.ops.iter() {
+            let inners = match op {
+                Op::Higher(_, inner) => vec![inner],
+                Op::Double(_, inner1, inner2) => vec![inner1, inner2],
+                Op::Basic(_) | Op::Literal(_) => vec![],
+            };
+            for inner in inners {
+                let inner_stats = inner.memo_stats();
+                stats.hits += inner_stats.hits;
+                stats.misses += inner_stats.misses;
+            }
+        }
+        stats
+    }
+}
+
+impl Callee for Code {
+    fn execute(&self, arg: Object) -> Object {
+        self.run(arg)
+    }
+    fn inverse_execute(&self, arg: Object) -> Object {
+        self.source.inverse_execute(arg)
+    }
+    fn as_affine(&self) -> Option<(i64, Num)> {
+        self.source.as_affine()
+    }
+    fn inverse(&self) -> Code {
+        build(&self.source.inverse(), None, self.track)
+    }
+}
+
+// Runs func on input with both the tree-walker and the VM, returning both results
+// if they disagree. Panics count as results, and running out of steps agrees with anything.
+pub fn differential(func: &Func, input: &Object, limit: Option<u64>) -> Option<(String, String)> {
+    let code = compile(func);
+    let describe = |result: Result<(Object, u64), _>| match result {
+        Ok((object, _)) => format!("{}", object),
+        Err(_) => "Panic".to_string(),
+    };
+    let (walked, compiled) = quietly(|| {
+        let walked = panic::catch_unwind(|| execute_with_limit(func, input.clone(), limit));
+        let compiled = panic::catch_unwind(|| execute_with_limit(&code, input.clone(), limit));
+        (describe(walked), describe(compiled))
+    });
+    let out_of_steps = format!("{}", Object::Error(OUT_OF_STEPS.to_string()));
+    if walked == compiled || walked == out_of_steps || compiled == out_of_steps {
+        None
+    } else {
+        Some((walked, compiled))
+    }
+}
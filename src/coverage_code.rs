@@ -1,6 +1,6 @@
 use crate::test_helpers::*;
-use crate::Object;
 use crate::Object::*;
+use crate::{execute_with_limit, lex, parse, Object, Seq, OUT_OF_STEPS};
 
 // The goal of this module is coverage of all nontrivial behavior of the execute functions
 
@@ -187,20 +187,24 @@ fn repeat_list() {
     let program = "rybtzzm";
     let input = int_to_obj(2);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        list_int_to_obj(vec![0, 1]),
-        lli_to_obj(vec![vec![], vec![0], vec![1], vec![0, 1]]),
-    ]);
+    let desired_output = List(
+        vec![
+            list_int_to_obj(vec![0, 1]),
+            lli_to_obj(vec![vec![], vec![0], vec![1], vec![0, 1]]),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
 #[test]
 fn inverse_double() {
     let program = "irmh";
-    let input = int_to_obj(3);
-    let output = run_prog(program, input);
-    let desired_output = lli_to_obj(vec![vec![-1, 0, 1], vec![-2, -1, 0], vec![-3, -2, -1]]);
+    let input = lli_to_obj(vec![vec![1, 2, 3], vec![2, 3, 4], vec![3, 4, 5]]);
+    let output = run_prog(program, input.clone());
+    let desired_output = List(vec![int_to_obj(3), list_int_to_obj(vec![0, 1, 2])].into());
     assert_eq!(desired_output, output);
+    assert_eq!(input, run_prog("rmh", output));
 }
 
 #[test]
@@ -210,7 +214,7 @@ fn order_error() {
     let output = run_prog(program, input);
     match output {
         List(list) => {
-            assert_eq!(3, list.len());
+            assert_eq!(3, list.len().unwrap());
             assert_eq!(list_int_to_obj(vec![]), list[0]);
             assert_eq!(list_int_to_obj(vec![1]), list[1]);
             assert!(matches!(list[2], Error(_)));
@@ -240,10 +244,145 @@ fn repeat_one() {
 #[test]
 fn inverse_while() {
     let program = "iwhh";
-    let input = int_to_obj(5);
-    let output = run_prog(program, input);
-    let desired_output = list_int_to_obj(vec![5, 4, 3, 2, 1]);
-    assert_eq!(desired_output, output);
+    let input = run_prog("whh", int_to_obj(-5));
+    let output = run_prog(program, input.clone());
+    assert_eq!(int_to_obj(-5), output);
+    assert_eq!(input, run_prog("whh", output));
+    assert!(matches!(run_prog(program, int_to_obj(5)), Error(_)));
+}
+
+#[test]
+fn inverse_bifurcate() {
+    let program = "ibhl";
+    let input = List(vec![int_to_obj(6), list_int_to_obj(vec![1, 0, 1])].into());
+    let output = run_prog(program, input.clone());
+    assert_eq!(int_to_obj(5), output);
+    assert_eq!(input, run_prog("bhl", output));
+    let disagree = List(vec![int_to_obj(6), list_int_to_obj(vec![1, 0, 0])].into());
+    assert_eq!(
+        Error("Inverse bifurcate halves disagree: 5 and 4".to_string()),
+        run_prog(program, disagree)
+    );
+    assert!(matches!(
+        run_prog(program, list_int_to_obj(vec![6])),
+        Error(_)
+    ));
+}
+
+#[test]
+fn inverse_search() {
+    // Deep index can't be undone directly when the indices for one element
+    // aren't a number, so a preimage is searched for.
+    let program = "ijqhpq";
+    let output = run_prog(program, int_to_obj(2));
+    assert_eq!(int_to_obj(2), run_prog("jqhpq", output));
+    // Errors the inverses define are kept, rather than searched past.
+    assert_eq!(
+        Error("Inverse tail of empty list".to_string()),
+        run_prog("iqttq", list_int_to_obj(vec![]))
+    );
+    assert_eq!(
+        Error("End (inverse head) of empty list".to_string()),
+        run_prog("ih", list_int_to_obj(vec![]))
+    );
+    // Two indices always give a list.
+    let program = "ijqbhhq";
+    assert_eq!(
+        Error("No preimage found within the step limit".to_string()),
+        run_prog(program, int_to_obj(3))
+    );
+    let func = parse(lex(program));
+    let (output, steps) = execute_with_limit(&func, int_to_obj(3), Some(1000));
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    assert!(steps > 1000);
+}
+
+#[test]
+fn search_order() {
+    let objects: Vec<String> = crate::enumerate::objects()
+        .take(10)
+        .map(|obj| obj.to_string())
+        .collect();
+    assert_eq!(
+        vec!["0", "1", "-1", "[]", "2", "-2", "3", "-3", "[0]", "4"],
+        objects
+    );
+}
+
+#[test]
+fn search_filters() {
+    use crate::enumerate::objects;
+    fn show(objects: impl Iterator<Item = Object>) -> Vec<String> {
+        objects.map(|obj| obj.to_string()).collect()
+    }
+    assert_eq!(
+        vec!["0", "1", "[]", "2", "3", "[0]"],
+        show(objects().max_size(2).non_negative())
+    );
+    assert_eq!(
+        vec!["[]", "[0]", "[0, 0]", "[1]", "[-1]"],
+        show(objects().max_size(3).flat().lists_only())
+    );
+    assert_eq!(
+        vec!["0", "1", "-1", "2"],
+        show(objects().ints_only().take(4))
+    );
+    assert_eq!(
+        vec!["1/2", "-1/2", "1/3", "-1/3", "3/2", "-3/2", "2/3"],
+        show(objects().rationals_only().take(7))
+    );
+    let hinted = objects()
+        .hints(vec![
+            int_to_obj(5),
+            int_to_obj(-1),
+            int_to_obj(5),
+            list_int_to_obj(vec![-1]),
+        ])
+        .non_negative()
+        .max_size(1);
+    assert_eq!(vec!["0", "1", "5", "[]"], show(hinted));
+    // Every object comes up once, however it was made.
+    let all: Vec<Object> = objects().max_size(6).collect();
+    let mut deduped = all.clone();
+    deduped.sort();
+    deduped.dedup();
+    assert_eq!(all.len(), deduped.len());
+}
+
+#[test]
+fn all_preimages() {
+    let factorizations: Vec<String> = crate::search::preimages(parse(lex("p")), int_to_obj(12))
+        .take(4)
+        .map(|obj| obj.to_string())
+        .collect();
+    assert_eq!(vec!["[12]", "[1, 12]", "[2, 6]", "[3, 4]"], factorizations);
+    // Two more than a half, which isn't part of the target.
+    let half = Object::from_str("1/2");
+    let first = crate::search::preimages(parse(lex("hh")), half.clone()).next();
+    assert_eq!(Some(Object::from_str("-3/2")), first);
+    let splits: Vec<String> =
+        crate::search::preimages(parse(lex("s")), list_int_to_obj(vec![1, 2, 3]))
+            .take(4)
+            .map(|obj| obj.to_string())
+            .collect();
+    assert_eq!(
+        vec![
+            "[[1, 2, 3]]",
+            "[1, [2, 3]]",
+            "[[], [1, 2, 3]]",
+            "[[1], [2, 3]]"
+        ],
+        splits
+    );
+    // Negation has one preimage, so the search for a second runs out.
+    let func = parse(lex("n"));
+    let (found, _) = crate::counting_steps(Some(10_000), || {
+        crate::search::preimages(func, int_to_obj(3)).collect::<Vec<_>>()
+    });
+    assert_eq!(vec![int_to_obj(-3)], found);
+    // Making ever bigger candidates doesn't end the search, only testing them does.
+    let truthy = crate::search::preimages(parse(lex("s")), int_to_obj(0));
+    assert_eq!(5000, truthy.take(5000).count());
 }
 
 #[test]
@@ -300,7 +439,7 @@ fn to_binary() {
 #[test]
 fn powerset_error() {
     let program = "y";
-    let input = int_to_obj(-1);
+    let input = Object::from_str("1/2");
     let output = run_prog(program, input);
     assert!(matches!(output, Error(_)));
 }
@@ -340,13 +479,19 @@ fn all_pairs_multi() {
     let program = "amm";
     let input = int_to_obj(3);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        List(vec![List(vec![list_int_to_obj(vec![]), int_to_obj(0)])]),
-        List(vec![
-            List(vec![list_int_to_obj(vec![]), int_to_obj(0)]),
-            List(vec![list_int_to_obj(vec![]), int_to_obj(1)]),
-        ]),
-    ]);
+    let desired_output = List(
+        vec![
+            List(vec![List(vec![list_int_to_obj(vec![]), int_to_obj(0)].into())].into()),
+            List(
+                vec![
+                    List(vec![list_int_to_obj(vec![]), int_to_obj(0)].into()),
+                    List(vec![list_int_to_obj(vec![]), int_to_obj(1)].into()),
+                ]
+                .into(),
+            ),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -355,11 +500,14 @@ fn all_pairs_multi_int() {
     let program = "asbzmm";
     let input = int_to_obj(3);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        list_int_to_obj(vec![]),
-        lli_to_obj(vec![vec![3, 0]]),
-        lli_to_obj(vec![vec![3, 0], vec![3, 1]]),
-    ]);
+    let desired_output = List(
+        vec![
+            list_int_to_obj(vec![]),
+            lli_to_obj(vec![vec![3, 0]]),
+            lli_to_obj(vec![vec![3, 0], vec![3, 1]]),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -368,10 +516,13 @@ fn all_pairs_multi_second() {
     let program = "asbbm";
     let input = int_to_obj(3);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        lli_to_obj(vec![vec![0, 3], vec![1, 3], vec![2, 3]]),
-        lli_to_obj(vec![vec![0, 3], vec![1, 3], vec![2, 3]]),
-    ]);
+    let desired_output = List(
+        vec![
+            lli_to_obj(vec![vec![0, 3], vec![1, 3], vec![2, 3]]),
+            lli_to_obj(vec![vec![0, 3], vec![1, 3], vec![2, 3]]),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -380,10 +531,13 @@ fn all_pairs_self() {
     let program = "am";
     let input = int_to_obj(2);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        List(vec![list_int_to_obj(vec![0, 1]), int_to_obj(0)]),
-        List(vec![list_int_to_obj(vec![0, 1]), int_to_obj(1)]),
-    ]);
+    let desired_output = List(
+        vec![
+            List(vec![list_int_to_obj(vec![0, 1]), int_to_obj(0)].into()),
+            List(vec![list_int_to_obj(vec![0, 1]), int_to_obj(1)].into()),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -495,19 +649,47 @@ fn reverse_lookup_neg() {
     assert_eq!(desired_output, output);
 }
 
+#[test]
+fn reverse_lookup_nested() {
+    let program = "vqhhq";
+    let output = run_prog(program, int_to_obj(3));
+    assert_eq!(int_to_obj(1), output);
+    // Head twice of a list is an int only if the list starts with a list.
+    let output = run_prog("vqhhtq", list_int_to_obj(vec![]));
+    let nested = List(vec![list_int_to_obj(vec![])].into());
+    assert_eq!(List(vec![int_to_obj(0), nested].into()), output);
+    // No length is negative.
+    let func = parse(lex("vl"));
+    let (output, _) = execute_with_limit(&func, int_to_obj(-1), Some(10_000));
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+}
+
+#[test]
+fn reverse_lookup_rational() {
+    let output = run_prog("vqpq", Object::from_str("[1/2]"));
+    assert_eq!(Object::from_str("1/2"), output);
+}
+
+#[test]
+fn reverse_lookup_large() {
+    // Ints this far out are found without enumerating as many lists.
+    let output = run_prog("vqhhq", int_to_obj(1_000_000));
+    assert_eq!(int_to_obj(999_998), output);
+}
+
 #[test]
 fn reverse_lookup_list() {
     let program = "mvp";
     let input = int_to_obj(7);
     let output = run_prog(program, input);
     let desired_output = lli_to_obj(vec![
-        vec![0, 1],
-        vec![1, 1],
-        vec![1, 2],
-        vec![1, 3],
-        vec![2, 2],
-        vec![1, 5],
-        vec![2, 3],
+        vec![0],
+        vec![],
+        vec![2],
+        vec![3],
+        vec![4],
+        vec![5],
+        vec![6],
     ]);
     assert_eq!(desired_output, output);
 }
@@ -530,6 +712,32 @@ fn deep_index() {
     assert_eq!(desired_output, output);
 }
 
+#[test]
+fn deep_index_int() {
+    let program = "jmh";
+    let output = run_prog(program, int_to_obj(4));
+    assert_eq!(list_int_to_obj(vec![1, 2, 3, 0]), output);
+    assert_eq!(output, run_prog(program, list_int_to_obj(vec![0, 1, 2, 3])));
+    assert!(matches!(run_prog("jh", int_to_obj(0)), Error(_)));
+}
+
+#[test]
+fn inverse_deep_index() {
+    let program = "ijqhtcq";
+    let input = list_int_to_obj(vec![7, 6, 5]);
+    let output = run_prog(program, input.clone());
+    assert_eq!(list_int_to_obj(vec![7, 5, 6]), output);
+    assert_eq!(input, run_prog("jqhtcq", output));
+    // The last element is never indexed, so can't be recovered.
+    let input = lli_to_obj(vec![vec![], vec![4], vec![4, 5]]);
+    assert!(matches!(run_prog("ijqmmq", input), Error(_)));
+    assert!(matches!(run_prog(program, int_to_obj(3)), Error(_)));
+    // A number index takes a number back to the list of just it.
+    let output = run_prog("ijt", int_to_obj(4));
+    assert_eq!(list_int_to_obj(vec![4]), output);
+    assert_eq!(int_to_obj(4), run_prog("jt", output));
+}
+
 #[test]
 fn deep_error() {
     let program = "jrtbhmqnm";
@@ -537,7 +745,7 @@ fn deep_error() {
     let output = run_prog(program, input);
     match output {
         List(list) => {
-            assert_eq!(4, list.len());
+            assert_eq!(4, list.len().unwrap());
             assert_eq!(list_int_to_obj(vec![1, 0]), list[0]);
             assert_eq!(list_int_to_obj(vec![0]), list[1]);
             assert_eq!(list_int_to_obj(vec![]), list[2]);
@@ -568,12 +776,15 @@ fn partitions() {
     let program = "d";
     let input = int_to_obj(3);
     let output = run_prog(program, input);
-    let desired_output = List(vec![
-        lli_to_obj(vec![vec![0, 1, 2]]),
-        lli_to_obj(vec![vec![0], vec![1, 2]]),
-        lli_to_obj(vec![vec![0, 1], vec![2]]),
-        lli_to_obj(vec![vec![0], vec![1], vec![2]]),
-    ]);
+    let desired_output = List(
+        vec![
+            lli_to_obj(vec![vec![0, 1, 2]]),
+            lli_to_obj(vec![vec![0], vec![1, 2]]),
+            lli_to_obj(vec![vec![0, 1], vec![2]]),
+            lli_to_obj(vec![vec![0], vec![1], vec![2]]),
+        ]
+        .into(),
+    );
     assert_eq!(desired_output, output);
 }
 
@@ -600,6 +811,407 @@ fn combine_empty() {
     let program = "mcmm";
     let input = int_to_obj(2);
     let output = run_prog(program, input);
-    let desired_output = List(vec![lli_to_obj(vec![]), lli_to_obj(vec![vec![0]])]);
+    let desired_output = List(vec![lli_to_obj(vec![]), lli_to_obj(vec![vec![0]])].into());
     assert_eq!(desired_output, output);
 }
+
+#[test]
+fn step_limit() {
+    let func = parse(lex("xh"));
+    let (output, steps) = execute_with_limit(&func, int_to_obj(0), Some(1000));
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    assert!(steps > 1000);
+}
+
+#[test]
+fn step_limit_unreached() {
+    let func = parse(lex("hs"));
+    let (output, steps) = execute_with_limit(&func, list_int_to_obj(vec![4, 5]), Some(1000));
+    assert_eq!(int_to_obj(10), output);
+    assert!(steps <= 1000);
+}
+
+#[test]
+fn shared_tails() {
+    let func = parse(lex("xt"));
+    let input = list_int_to_obj((0..500).collect());
+    let (output, steps) = execute_with_limit(&func, input, Some(100_000));
+    match output {
+        List(l) => {
+            assert_eq!(501, l.len().unwrap());
+            assert_eq!(list_int_to_obj(vec![499]), l[499]);
+        }
+        _ => panic!("Fixed point is a list"),
+    }
+    assert!(steps < 100_000);
+}
+
+#[test]
+fn shared_storage() {
+    let list = Seq::from(vec![int_to_obj(1), int_to_obj(2), int_to_obj(3)]);
+    let tail = list.tail();
+    assert!(std::ptr::eq(&list[1], &tail[0]));
+    assert_eq!(vec![int_to_obj(2), int_to_obj(3)], tail.into_vec());
+    assert_eq!(vec![int_to_obj(1), int_to_obj(2)], list.init().to_vec());
+    assert_eq!(3, list.into_vec().len());
+}
+
+#[test]
+fn hashing_is_pure() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let hash = |obj: &Object| {
+        let mut hasher = DefaultHasher::new();
+        obj.hash(&mut hasher);
+        hasher.finish()
+    };
+    let list = lli_to_obj(vec![vec![1, 2], vec![3]]);
+    let (limited, steps) = crate::counting_steps(Some(0), || (hash(&list), list.clone()));
+    assert_eq!((hash(&list), list), limited);
+    assert_eq!(0, steps);
+}
+
+#[test]
+fn lazy_ranges() {
+    let big = Object::from_str("1000000000000000000000000");
+    let cases = [
+        ("lmh", "1000000000000000000000000"),
+        ("hmh", "1"),
+        ("hnmh", "1000000000000000000000000"),
+        ("smt", "499999999999999999999998500000000000000000000000"),
+        ("hnmn", "-999999999999999999999999"),
+        ("htttmh", "4"),
+        ("jkmhh", "1"),
+        ("jhmh", "2"),
+    ];
+    for (program, expected) in cases {
+        let func = parse(lex(program));
+        let (output, steps) = execute_with_limit(&func, big.clone(), Some(1000));
+        assert_eq!(Object::from_str(expected), output, "{}", program);
+        assert!(steps < 1000);
+    }
+}
+
+#[test]
+fn lazy_ranges_built() {
+    let output = run_prog("mh", int_to_obj(3));
+    assert_eq!(list_int_to_obj(vec![1, 2, 3]), output);
+    let output = run_prog("nmh", int_to_obj(-3));
+    assert_eq!(list_int_to_obj(vec![1, 2, 3]), output);
+    let output = run_prog("fh", int_to_obj(4));
+    assert_eq!(list_int_to_obj(vec![0, 1, 2, 3]), output);
+    assert_eq!(run_prog("mh", int_to_obj(4)), run_prog("mh", int_to_obj(4)));
+    assert_eq!(
+        list_int_to_obj(vec![2, 3, 4, 5]),
+        run_prog("tmh", int_to_obj(5))
+    );
+    assert_eq!(int_to_obj(6), run_prog("sm", int_to_obj(-4)));
+}
+
+#[test]
+fn lazy_ranges_built_after_limit() {
+    let range = Seq::upto(1000.into());
+    let (_, steps) = crate::counting_steps(Some(100), || range.to_vec());
+    assert!(steps <= 101);
+    assert_eq!(1000, range.to_vec().len());
+    assert_eq!(int_to_obj(999), range[999]);
+    let func = parse(lex("lhwiehzzhzil"));
+    let (output, _) = execute_with_limit(&func, int_to_obj(200_000), None);
+    assert_eq!(int_to_obj(200_000), output);
+}
+
+#[test]
+fn lazy_generators() {
+    let cases = [
+        ("hc", "20", "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19]"),
+        ("lc", "20", "2432902008176640000"),
+        ("le", "10", "9864101"),
+        ("ld", "30", "536870912"),
+        ("lhd", "30", "1"),
+        ("ly", "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40]", "1099511627776"),
+        ("hfqstttttttttihqc", "12", "[0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 9]"),
+        ("jqhhkqc", "12", "[0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 9, 11]"),
+        ("htc", "20", "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 19, 18]"),
+        ("hfqstttq", "1000000000000000000000000", "3"),
+    ];
+    for (program, input, expected) in cases {
+        let func = parse(lex(program));
+        let input = Object::from_str(input);
+        let (output, steps) = execute_with_limit(&func, input, Some(1000));
+        assert_eq!(Object::from_str(expected), output, "{}", program);
+        assert!(steps < 1000);
+    }
+}
+
+#[test]
+fn lazy_generators_built() {
+    let func = parse(lex("e"));
+    let (output, _) = execute_with_limit(&func, int_to_obj(3), Some(1000));
+    let expected = "[[], [0], [1], [2], [0, 1], [0, 2], [1, 0], [1, 2], [2, 0], [2, 1], \
+        [0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]]";
+    assert_eq!(Object::from_str(expected), output);
+    let (output, _) = execute_with_limit(&func, int_to_obj(10), Some(1000));
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    let output = run_prog("fqsttq", int_to_obj(5));
+    assert_eq!(list_int_to_obj(vec![2]), output);
+    // A filter that keeps nothing stops scanning once out of steps.
+    let func = parse(lex("fk"));
+    let input = Object::from_str("1000000000000000000000000");
+    let (output, _) = execute_with_limit(&func, input.clone(), Some(1000));
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    // What the filter made so far isn't taken as the whole list, by either interpreter.
+    for program in ["hfk", "qefkfkq"] {
+        let func = parse(lex(program));
+        let (output, _) = crate::counting_steps(Some(1000), || func.execute(input.clone()));
+        assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+        let code = crate::compile::compile(&func);
+        let (output, _) = crate::counting_steps(Some(1000), || code.run(input.clone()));
+        assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    }
+}
+
+#[test]
+fn unrank_combinatorics() {
+    let hundred = format!("{:?}", (0..100).collect::<Vec<i64>>());
+    let cases = [
+        ("jtc", "20".to_string(), "[19, 18, 17, 16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]"),
+        ("hjtc", "30".to_string(), "29"),
+        ("jte", "4".to_string(), "[3, 2, 1, 0]"),
+        ("jqhhhhhkqe", "30".to_string(), "[4]"),
+        ("ljtd", "100".to_string(), "100"),
+        ("ljty", hundred.clone(), "100"),
+        ("jhy", hundred, "[0]"),
+        ("hjqttkqc", "25".to_string(), "24"),
+        ("htc", "30".to_string(), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 29, 28]"),
+    ];
+    for (program, input, expected) in cases {
+        let func = parse(lex(program));
+        let input = Object::from_str(&input);
+        let (output, steps) = execute_with_limit(&func, input, Some(1000));
+        assert_eq!(Object::from_str(expected), output, "{}", program);
+        assert!(steps < 1000);
+    }
+}
+
+#[test]
+fn combinatorics_built_after_limit() {
+    use crate::combinatorics::{permutations, subsets};
+    let base = || Seq::upto(5.into());
+    for (l, len) in [(permutations(base()), 120), (subsets(base()), 32)] {
+        let l = l.unwrap();
+        let (partial, _) = crate::counting_steps(Some(20), || l.clone().into_vec());
+        assert!(partial.len() < len);
+        assert_eq!(len, l.to_vec().len());
+        assert_eq!(crate::number::Num::from(len), l.count());
+    }
+    // Counting the arrangements takes a step per length.
+    let (counted, _) =
+        crate::counting_steps(Some(3), || crate::combinatorics::arrangements(base()));
+    assert_eq!(Err(Error(OUT_OF_STEPS.to_string())), counted.map(List));
+}
+
+#[test]
+fn overlong_lists() {
+    // 25! permutations, and 2^128 ints, are too many to hold in memory.
+    let too_long = Error(crate::TOO_LONG.to_string());
+    assert_eq!(too_long, run_prog("yc", int_to_obj(25)));
+    assert_eq!(too_long, run_prog("cyy", int_to_obj(7)));
+    let permutations = run_prog("c", int_to_obj(25));
+    assert_eq!(too_long, parse(lex("jk")).invert(permutations));
+    // Walking them runs out of steps instead.
+    for program in ["ac", "rc", "uhc"] {
+        let func = parse(lex(program));
+        let (output, _) = execute_with_limit(&func, int_to_obj(25), Some(1000));
+        assert!(matches!(output, Error(_)), "{}: {:?}", program, output);
+    }
+}
+
+#[test]
+fn object_ordering() {
+    let nested = |objects: Vec<Object>| List(objects.into());
+    let one_then_list = nested(vec![int_to_obj(1), list_int_to_obj(vec![0])]);
+    let list_first = nested(vec![list_int_to_obj(vec![0])]);
+    let head_error = Error("Head of empty list".to_string());
+    let divide_error = Error("Divide by zero".to_string());
+    let mut objects = vec![
+        one_then_list.clone(),
+        list_int_to_obj(vec![]),
+        head_error.clone(),
+        int_to_obj(3),
+        list_int_to_obj(vec![1, 2]),
+        int_to_obj(-4),
+        list_first.clone(),
+        list_int_to_obj(vec![1]),
+        divide_error.clone(),
+    ];
+    objects.sort();
+    let expected = vec![
+        int_to_obj(-4),
+        int_to_obj(3),
+        list_int_to_obj(vec![]),
+        list_int_to_obj(vec![1]),
+        list_int_to_obj(vec![1, 2]),
+        one_then_list,
+        list_first,
+        divide_error,
+        head_error,
+    ];
+    assert_eq!(expected, objects);
+}
+
+#[test]
+fn errors_sort_together() {
+    // Head of an empty list and length of a fraction are both errors, so they're alike.
+    let input = Object::from_str("[[1/2], [], [3], []]");
+    let output = run_prog("gqlhq", input.clone());
+    let lists = Object::from_str("[[1/2], [], []]");
+    let expected = List(vec![Object::from_str("[[3]]"), lists].into());
+    assert_eq!(expected, output);
+    let output = run_prog("oqlhq", input);
+    assert_eq!(Object::from_str("[[3], [1/2], [], []]"), output);
+    // Ord still tells errors apart, as == does.
+    let head = Error("Head of empty list".to_string());
+    let length = Error("Length of a fraction".to_string());
+    assert!(head < length);
+    assert!(head.key_cmp(&length).is_eq());
+}
+
+#[test]
+fn inverse_basic_table() {
+    let cases = [
+        ("it", "5", "6"),
+        ("in", "[1, 2, 3]", "[3, 2, 1]"),
+        ("in", "4", "-4"),
+        ("iy", "1024", "10"),
+        ("iy", "1000", "9"),
+        ("iyy", "[0, 1, 2]", "[0, 1, 2]"),
+        ("ic", "[[1, 2], [3, 4]]", "[[1, 3], [2, 4]]"),
+        ("il", "4", "[0, 1, 2, 3]"),
+        ("id", "[3, 1, 2]", "[3, 1, 2]"),
+        ("ie", "1", "[]"),
+        ("ie", "0", "[0, 1]"),
+        ("iee", "3", "3"),
+        ("iaa", "4", "4"),
+        ("iaa", "[1, 2]", "[1, 2]"),
+        ("ip", "[2, 2, 3]", "12"),
+        ("ipp", "360", "360"),
+        ("ipp", "12", "12"),
+        ("ipp", "30", "30"),
+        // Any pair is divided, even the factors of a product of two primes.
+        ("ipp", "6", "2/3"),
+        ("ip", "[3, 2]", "3/2"),
+        ("ip", "[2, 4]", "1/2"),
+        ("ik", "0", "0"),
+    ];
+    for (program, input, expected) in cases {
+        let output = run_prog(program, Object::from_str(input));
+        assert_eq!(Object::from_str(expected), output, "{}", program);
+    }
+    let output = run_prog("ip", Object::from_str("[[0, 3], [0, 4], [1, 3], [1, 4]]"));
+    assert_eq!(lli_to_obj(vec![vec![0, 1], vec![3, 4]]), output);
+    let errors = [
+        ("id", "[1, 1]"),
+        ("ik", "3"),
+        ("ic", "3"),
+        ("iy", "0"),
+        ("ia", "[1, 2]"),
+        ("ie", "2"),
+        ("il", "-1"),
+    ];
+    for (program, input) in errors {
+        let output = run_prog(program, Object::from_str(input));
+        assert!(matches!(output, Error(_)), "{}: {:?}", program, output);
+    }
+}
+
+#[test]
+fn inverse_sequences() {
+    assert_eq!(int_to_obj(3), run_prog("iqhhq", int_to_obj(5)));
+    assert_eq!(int_to_obj(5), run_prog("iqhtq", int_to_obj(5)));
+    // h then n, undone: n then h inverted.
+    assert_eq!(int_to_obj(-6), run_prog("iqnhq", int_to_obj(5)));
+    for program in ["hh", "ht", "hn", "nh", "tnh", "hnt", "nyh"] {
+        for i in [0, 3, -7] {
+            if program == "nyh" && i < -1 {
+                // No power of two is negative.
+                continue;
+            }
+            let forward = run_prog(program, int_to_obj(i));
+            let back = run_prog(&format!("iq{}q", program), forward.clone());
+            assert_eq!(
+                int_to_obj(i),
+                back,
+                "{} on {}, via {:?}",
+                program,
+                i,
+                forward
+            );
+        }
+    }
+}
+
+#[test]
+fn inverse_higher_error() {
+    let error = Error("Inverse tail of empty list".to_string());
+    for higher in "fgijmoruvx".chars() {
+        let func = parse(lex(&format!("{}h", higher)));
+        assert_eq!(error, func.invert(error.clone()), "{}h", higher);
+    }
+}
+
+#[test]
+fn inverse_group() {
+    let program = "igqllq";
+    let input = lli_to_obj(vec![vec![1], vec![2, 3], vec![4, 5, 6, 7]]);
+    let output = run_prog(program, input.clone());
+    assert_eq!(list_int_to_obj(vec![1, 2, 3, 4, 5, 6, 7]), output);
+    assert_eq!(input, run_prog("gqllq", output));
+}
+
+#[test]
+fn inverse_fixed_point() {
+    let program = "ixs";
+    let input = run_prog("xs", int_to_obj(5));
+    assert_eq!(list_int_to_obj(vec![5, 0, 1]), input);
+    let output = run_prog(program, input);
+    assert_eq!(int_to_obj(1), output);
+    assert!(matches!(
+        run_prog(program, list_int_to_obj(vec![])),
+        Error(_)
+    ));
+}
+
+#[test]
+fn inverse_repeat() {
+    let program = "irh";
+    let output = run_prog(program, list_int_to_obj(vec![5, 6, 7]));
+    assert_eq!(list_int_to_obj(vec![3, 4]), output);
+    assert_eq!(list_int_to_obj(vec![5, 6, 7]), run_prog("rh", output));
+    let output = run_prog(program, list_int_to_obj(vec![4, 5, 6]));
+    assert_eq!(int_to_obj(3), output);
+    assert!(matches!(run_prog(program, int_to_obj(3)), Error(_)));
+    // The inverse of the first result failing fails the whole.
+    assert_eq!(
+        Error("Inverse tail of empty list".to_string()),
+        run_prog("irt", lli_to_obj(vec![vec![]]))
+    );
+}
+
+#[test]
+fn inverse_update() {
+    let program = "iuh";
+    let input = list_int_to_obj(vec![5, 4, 6]);
+    let output = run_prog(program, input.clone());
+    let desired_output = lli_to_obj(vec![vec![0, 1, 2], vec![4, 3, 5]]);
+    assert_eq!(desired_output, output);
+    assert_eq!(input, run_prog("uh", output));
+    assert!(matches!(run_prog(program, int_to_obj(3)), Error(_)));
+}
+
+#[test]
+fn inverse_reverse_lookup() {
+    let program = "ivy";
+    let output = run_prog(program, int_to_obj(5));
+    assert_eq!(int_to_obj(32), output);
+    assert_eq!(int_to_obj(5), run_prog("vy", output));
+}
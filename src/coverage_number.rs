@@ -0,0 +1,183 @@
+use crate::number::Num;
+use crate::test_helpers::*;
+use crate::Object;
+
+use num_bigint::BigInt;
+use std::collections::HashSet;
+
+fn big(string: &str) -> Num {
+    string.parse().unwrap()
+}
+
+#[test]
+fn promote_on_overflow() {
+    let max = Num::from(i64::MAX);
+    let min = Num::from(i64::MIN);
+    assert_eq!(big("9223372036854775808"), &max + 1);
+    assert_eq!(big("-9223372036854775809"), &min - 1);
+    assert_eq!(big("9223372036854775808"), -&min);
+    assert_eq!(big("9223372036854775808"), min.abs());
+    assert_eq!(big("85070591730234615847396907784232501249"), &max * &max);
+    let minus_one = Num::from(-1);
+    assert_eq!(big("9223372036854775808"), &min / &minus_one);
+    assert_eq!(Num::from(0), &min % &minus_one);
+}
+
+#[test]
+fn demote_when_small() {
+    let past_max = Num::from(i64::MAX) + 1;
+    assert!(matches!(past_max, Num::Big(_)));
+    let back = past_max - 1;
+    assert_eq!(Num::Small(i64::MAX), back);
+    assert_eq!(Num::Small(5), Num::from(BigInt::from(5)));
+    let set: HashSet<Num> = vec![back, Num::from(i64::MAX)].into_iter().collect();
+    assert_eq!(1, set.len());
+}
+
+#[test]
+fn compare_mixed() {
+    let huge = big("100000000000000000000");
+    let tiny = big("-100000000000000000000");
+    assert!(Num::from(0) < huge);
+    assert!(huge > i64::MAX);
+    assert!(tiny < i64::MIN);
+    assert!(tiny < huge);
+    assert!(huge > 1);
+}
+
+#[test]
+fn unbounded_programs() {
+    let input = Object::from_str("9223372036854775807");
+    let output = run_prog("h", input);
+    assert_eq!(Object::from_str("9223372036854775808"), output);
+    let output = run_prog("y", int_to_obj(64));
+    assert_eq!(Object::from_str("18446744073709551616"), output);
+    let output = run_prog("p", Object::from_str("[4294967296, 4294967296]"));
+    assert_eq!(Object::from_str("18446744073709551616"), output);
+    let output = run_prog("ns", Object::from_str("[-9223372036854775808, -1]"));
+    assert_eq!(Object::from_str("9223372036854775809"), output);
+}
+
+#[test]
+fn big_primes() {
+    let prime = "1000000000000000000000000000057";
+    assert_eq!(int_to_obj(1), run_prog("ip", Object::from_str(prime)));
+    let output = run_prog("p", Object::from_str(prime));
+    assert_eq!(Object::from_str(&format!("[{}]", prime)), output);
+    // Carmichael numbers, and the smallest strong pseudoprime to base 2.
+    for composite in ["561", "41041", "2047", "3215031751"] {
+        assert_eq!(int_to_obj(0), run_prog("ip", Object::from_str(composite)));
+    }
+    let output = run_prog(
+        "ip",
+        Object::from_str("170141183460469231731687303715884105727"),
+    );
+    assert_eq!(int_to_obj(1), output);
+    let output = run_prog(
+        "ip",
+        Object::from_str("170141183460469231731687303715884105729"),
+    );
+    assert_eq!(int_to_obj(0), output);
+    // A strong pseudoprime to the first 13 prime bases, which the Lucas test catches.
+    let output = run_prog("ip", Object::from_str("3317044064679887385961981"));
+    assert_eq!(int_to_obj(0), output);
+}
+
+#[test]
+fn big_factors() {
+    let output = run_prog("p", Object::from_str("1000000016000000063"));
+    assert_eq!(Object::from_str("[1000000007, 1000000009]"), output);
+    let output = run_prog("p", Object::from_str("123456789012345678901234567890"));
+    let expected = "[2, 3, 3, 3, 5, 7, 13, 31, 37, 211, 241, 2161, 3607, 3803, 2906161]";
+    assert_eq!(Object::from_str(expected), output);
+    let output = run_prog("p", Object::from_str("-9223372036854775808"));
+    assert_eq!(Object::from_str(&format!("{:?}", vec![2; 63])), output);
+    let output = run_prog("pp", Object::from_str("[1000003, 1000003]"));
+    assert_eq!(Object::from_str("[1000003, 1000003]"), output);
+}
+
+#[test]
+fn rationals_parse_and_sort() {
+    let half = Object::from_str("2/4");
+    assert_eq!("1/2", half.to_string());
+    assert_eq!(int_to_obj(2), Object::from_str("4/2"));
+    assert_eq!(
+        "[-3/2, [1/3]]",
+        Object::from_str("[-6/4, [1/3]]").to_string()
+    );
+    let mut objects = vec![
+        list_int_to_obj(vec![]),
+        int_to_obj(1),
+        half,
+        Object::from_str("-1/3"),
+        int_to_obj(0),
+    ];
+    objects.sort();
+    let expected = vec![
+        Object::from_str("-1/3"),
+        int_to_obj(0),
+        Object::from_str("1/2"),
+        int_to_obj(1),
+        list_int_to_obj(vec![]),
+    ];
+    assert_eq!(expected, objects);
+}
+
+#[test]
+fn rational_functions() {
+    let cases = [
+        ("y", "-3", "1/8"),
+        ("hy", "-1", "3/2"),
+        ("t", "1/2", "-1/2"),
+        ("n", "2/3", "-2/3"),
+        ("s", "1/2", "0"),
+        ("s", "[1/2, 1/3, 1]", "11/6"),
+        ("s", "[1/2, 1/2]", "1"),
+        ("p", "[2/3, 3/4]", "1/2"),
+        ("p", "-3/4", "[1/2, 1/2, 3]"),
+        ("p", "4/15", "[1/5, 1/3, 2, 2]"),
+        ("ip", "[3, 4]", "3/4"),
+        ("ip", "[12, -4]", "-3"),
+        ("ipp", "5/12", "5/12"),
+        ("ip", "1/2", "0"),
+        ("iy", "1/8", "-3"),
+        ("iy", "3/8", "-2"),
+        ("iyy", "-5", "-5"),
+        ("ih", "1/2", "-1/2"),
+        ("m", "5/2", "[0, 1, 2]"),
+        ("ot", "[1, 1/2, -1/3]", "[-1/3, 1/2, 1]"),
+    ];
+    for (program, input, expected) in cases {
+        let output = run_prog(program, Object::from_str(input));
+        assert_eq!(Object::from_str(expected), output, "{}", program);
+    }
+    for (program, input) in [("y", "1/2"), ("l", "1/2"), ("iy", "-1/2"), ("ip", "[1, 0]")] {
+        let output = run_prog(program, Object::from_str(input));
+        assert!(matches!(output, Object::Error(_)), "{}", program);
+    }
+}
+
+#[test]
+fn rational_indices() {
+    // Indices and counts round their magnitude up, negative or not.
+    let cases = [
+        ("uh", "[-1/2, [5, 6, 7]]", "[5, 6, 8]"),
+        ("uh", "[1/2, [5, 6, 7]]", "[5, 7, 7]"),
+        ("uh", "[[-3/2, 1/2], [5, 6, 7]]", "[5, 8, 7]"),
+        // -4/3 indexes as -2.
+        ("jqipbhnq", "[5, 6, 7]", "6"),
+        ("rh", "[5/2, 0]", "[1, 2, 3]"),
+        ("rh", "[-5/2, 0]", "[]"),
+        ("m", "-5/2", "[2, 1, 0]"),
+    ];
+    for (program, input, expected) in cases {
+        let output = run_prog(program, Object::from_str(input));
+        assert_eq!(
+            Object::from_str(expected),
+            output,
+            "{} on {}",
+            program,
+            input
+        );
+    }
+}
@@ -233,3 +233,27 @@ fn quote_alone_front() {
     ];
     assert_eq!(funcs, Func::Bound(desired_funcs));
 }
+#[test]
+fn bind_reached_front() {
+    let program = "hz";
+    let result = try_parse(lex(program));
+    assert_eq!(Err(ParseError::BindReachedFront), result);
+}
+#[test]
+fn bind_reached_quote() {
+    let program = "mqhzq";
+    let result = try_parse(lex(program));
+    assert_eq!(Err(ParseError::BindReachedQuote), result);
+}
+#[test]
+fn solo_quote_unbound() {
+    let program = "hq";
+    let result = try_parse(lex(program));
+    assert_eq!(Err(ParseError::SoloQuoteUnbound), result);
+}
+#[test]
+fn unknown_char() {
+    let program = "hs1";
+    let result = try_lex(program).map(|_| ());
+    assert_eq!(Err(ParseError::UnknownChar(2, '1')), result);
+}
@@ -0,0 +1,421 @@
+use crate::compile::*;
+use crate::neighbours::*;
+use crate::optimize::*;
+use crate::suggest::*;
+use crate::test_helpers::*;
+use crate::Object::*;
+use crate::{
+    execute_with_limit, lex, parse, try_lex, try_parse, BasicFunc, Func, HigherFunc, Object,
+    ParseError, Seq, OUT_OF_STEPS,
+};
+
+// The goal of this module is coverage of the tools built on top of the interpreter
+
+#[test]
+fn single_edits_deduplicated() {
+    let edits = single_edits("hs");
+    // 2 deletions, 50 substitutions, 78 insertions, less "hhs" and "hss" found twice.
+    assert_eq!(128, edits.len());
+    assert!(edits.iter().all(|(edited, _)| edited != "hs"));
+    assert_eq!(("s".to_string(), Edit::Delete(0, 'h')), edits[0]);
+}
+
+#[test]
+fn evaluate_outcomes() {
+    let input = int_to_obj(0);
+    assert_eq!(
+        Outcome::Output(int_to_obj(1)),
+        evaluate("h", &input, 100, Mode::default())
+    );
+    assert!(matches!(
+        evaluate("tm", &input, 100, Mode::default()),
+        Outcome::RuntimeError(_)
+    ));
+    assert!(matches!(
+        evaluate("xh", &input, 100, Mode::default()),
+        Outcome::RuntimeError(_)
+    ));
+    assert_eq!(
+        Outcome::ParseError(ParseError::BindReachedFront),
+        evaluate("z", &input, 100, Mode::default())
+    );
+}
+
+#[test]
+fn evaluate_panic() {
+    let input = int_to_obj(0);
+    let outcome = quietly(|| evaluate("mhiyz", &input, 100, Mode::default()));
+    assert!(matches!(outcome, Outcome::RuntimeError(_)));
+}
+
+#[test]
+fn neighbours_grouped() {
+    let report = neighbours("h", &int_to_obj(1));
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!("h => 2", lines[0]);
+    let two = lines.iter().position(|line| *line == "2").unwrap();
+    assert_eq!("    y  sub 0 h->y", lines[two + 1]);
+    let zero = lines.iter().position(|line| *line == "0").unwrap();
+    assert_eq!("    j  sub 0 h->j", lines[zero + 1]);
+    assert_eq!("    k  sub 0 h->k", lines[zero + 2]);
+    let parse_error = lines
+        .iter()
+        .position(|line| *line == "Parse error: z found no higher-order function to bind to")
+        .unwrap();
+    assert_eq!("    z  sub 0 h->z", lines[parse_error + 1]);
+    assert!(lines[..parse_error - 1]
+        .iter()
+        .any(|line| line.starts_with("Runtime error")));
+}
+
+#[test]
+fn suggest_parse_error() {
+    let failure = Outcome::ParseError(ParseError::BindReachedFront);
+    let found = suggestions("hz", &int_to_obj(3), &failure, None, Mode::default());
+    assert_eq!("h", found[0].program);
+    assert_eq!(Edit::Delete(1, 'z'), found[0].edit);
+    assert_eq!(int_to_obj(4), found[0].output);
+}
+
+#[test]
+fn suggest_balance_quote() {
+    let failure = Outcome::ParseError(ParseError::SoloQuoteUnbound);
+    let found = suggestions("hq", &int_to_obj(1), &failure, None, Mode::default());
+    assert_eq!("qhq", found[0].program);
+    assert_eq!(int_to_obj(2), found[0].output);
+}
+
+#[test]
+fn suggest_expected_first() {
+    let failure = Outcome::RuntimeError("Tail of empty list".to_string());
+    let expected = int_to_obj(-1);
+    let found = suggestions(
+        "tm",
+        &int_to_obj(0),
+        &failure,
+        Some(&expected),
+        Mode::default(),
+    );
+    assert_eq!("t", found[0].program);
+    assert!(found[0].matches_expected);
+    assert!(found
+        .iter()
+        .skip_while(|suggestion| suggestion.matches_expected)
+        .all(|suggestion| !suggestion.matches_expected));
+}
+
+#[test]
+fn suggest_in_mode_run() {
+    let failure = Outcome::ParseError(ParseError::BindReachedFront);
+    let reverse = Mode {
+        reverse: true,
+        optimize: false,
+    };
+    let found = suggestions("hz", &int_to_obj(3), &failure, None, reverse);
+    assert_eq!("h", found[0].program);
+    assert_eq!(int_to_obj(2), found[0].output);
+    let optimized = Mode {
+        reverse: false,
+        optimize: true,
+    };
+    let searched = evaluate("ijqhpq", &int_to_obj(2), 100_000, Mode::default());
+    assert!(matches!(searched, Outcome::Output(_)));
+    assert_eq!(
+        searched,
+        evaluate("ijqhpq", &int_to_obj(2), 100_000, optimized)
+    );
+}
+
+#[test]
+fn suggest_head_tail_swap() {
+    let failure = Outcome::RuntimeError("Tail of empty list".to_string());
+    let found = suggestions("ttm", &int_to_obj(1), &failure, None, Mode::default());
+    assert_eq!("thm", found[0].program);
+    assert_eq!(Edit::Substitute(1, 't', 'h'), found[0].edit);
+}
+
+fn optimized(program: &str, input: &Object) -> Func {
+    optimize(parse(lex(program)), input)
+}
+
+#[test]
+fn optimize_flatten() {
+    let output = optimized("hqhhq", &int_to_obj(0));
+    let head = Func::Basic(BasicFunc::Head);
+    assert_eq!(Func::Bound(vec![head.clone(), head.clone(), head]), output);
+}
+
+#[test]
+fn optimize_negate_twice() {
+    let output = optimized("hnnl", &int_to_obj(0));
+    let desired_output = Func::Bound(vec![
+        Func::Basic(BasicFunc::Head),
+        Func::Basic(BasicFunc::Length),
+    ]);
+    assert_eq!(desired_output, output);
+}
+
+#[test]
+fn optimize_head_tail() {
+    let output = optimized("mhtz", &int_to_obj(3));
+    let desired_output = Func::Bound(vec![Func::Higher(
+        HigherFunc::Map,
+        Box::new(Func::Bound(vec![])),
+    )]);
+    assert_eq!(desired_output, output);
+    // On a list, ht is not the identity.
+    let output = optimized("ht", &list_int_to_obj(vec![1, 2]));
+    assert_eq!(parse(lex("ht")), output);
+}
+
+#[test]
+fn optimize_double_inverse() {
+    let output = optimized("iiy", &int_to_obj(0));
+    assert_eq!(Func::Bound(vec![Func::Basic(BasicFunc::PowerSet)]), output);
+}
+
+#[test]
+fn optimize_fold_constant() {
+    let output = optimized("hyhkmh", &int_to_obj(0));
+    assert_eq!(Func::Bound(vec![Func::Literal(int_to_obj(3))]), output);
+    let output = optimized("mhbkk", &int_to_obj(0));
+    let desired_output = Func::Bound(vec![Func::Literal(list_int_to_obj(vec![1, 1]))]);
+    assert_eq!(desired_output, output);
+}
+
+#[test]
+fn optimize_no_fold_under_inverse() {
+    let output = optimized("ihkz", &int_to_obj(0));
+    assert_eq!(parse(lex("ihkz")), output);
+}
+
+#[test]
+fn optimize_no_fold_forever() {
+    let output = optimized("xhk", &int_to_obj(0));
+    assert_eq!(parse(lex("xhk")), output);
+}
+
+#[test]
+fn optimize_preserves_results() {
+    let cases = vec![
+        ("ttsmzyhhyhh", int_to_obj(0)),
+        ("ihsrbshnbms", int_to_obj(9)),
+        ("htnrbhqbht", list_int_to_obj(vec![5, -1])),
+        ("smeboqcbmqpbhhqr", int_to_obj(10)),
+        ("mmihgqhhonsqcbmnhzlz", list_int_to_obj(vec![5, 6, 8, 3, 9])),
+        (
+            "iholfebdzzmqsmpmbqmqmscumnbkqfqseboqqpbqzypcmb",
+            int_to_obj(2),
+        ),
+        ("mkrtbztm", int_to_obj(3)),
+        ("nnhthtmnnhtz", int_to_obj(4)),
+        ("ijqhpq", int_to_obj(2)),
+        // Undoing an inverse runs the func, like the func it reduces to.
+        ("iih", list_int_to_obj(vec![])),
+        ("iqihq", list_int_to_obj(vec![])),
+    ];
+    for (program, input) in cases {
+        let func = parse(lex(program));
+        let desired_output = func.execute(input.clone());
+        let output = optimize(func, &input).execute(input);
+        assert_eq!(desired_output, output, "Program: {}", program);
+    }
+}
+
+#[test]
+fn compiled_matches_tree_walker() {
+    let inputs = vec![
+        int_to_obj(0),
+        int_to_obj(4),
+        int_to_obj(-3),
+        list_int_to_obj(vec![3, 1, 2]),
+        lli_to_obj(vec![vec![1, 2], vec![3]]),
+    ];
+    let mut programs: Vec<String> = vec![];
+    for first in ATOMS.chars() {
+        programs.push(first.to_string());
+        for second in ATOMS.chars() {
+            programs.push(format!("{}{}", first, second));
+        }
+    }
+    programs.extend(
+        [
+            "ttsmzyhhyhh",
+            "ihsrbshnbms",
+            "htnrbhqbht",
+            "smeboqcbmqpbhhqr",
+            "mmihgqhhonsqcbmnhzlz",
+            "mkrtbztm",
+            "qhmqhtqqmzhq",
+        ]
+        .iter()
+        .map(|program| program.to_string()),
+    );
+    for program in programs {
+        let func = match try_lex(&program).and_then(try_parse) {
+            Ok(func) => func,
+            Err(_) => continue,
+        };
+        for input in &inputs {
+            let mismatch = differential(&func, input, Some(1_000));
+            assert_eq!(None, mismatch, "Program: {} on {}", program, input);
+        }
+    }
+}
+
+#[test]
+fn compiled_flattens_sequences() {
+    let head = || Func::Basic(BasicFunc::Head);
+    let func = Func::Bound(vec![
+        Func::Bound(vec![head(), Func::Bound(vec![head()])]),
+        Func::Bound(vec![head(), head()]),
+    ]);
+    let code = compile(&func);
+    let (output, steps) = execute_with_limit(&code, int_to_obj(0), None);
+    assert_eq!(int_to_obj(4), output);
+    assert_eq!(4, steps);
+}
+
+#[test]
+fn memo_reuses_results() {
+    let func = parse(lex("mqlcq"));
+    let input = list_int_to_obj(vec![5, 5, 5, 5]);
+    let plain = compile(&func);
+    let (expected, plain_steps) = execute_with_limit(&plain, input.clone(), None);
+    let code = compile_memoized(&func, 100);
+    let (output, steps) = execute_with_limit(&code, input, None);
+    assert_eq!(expected, output);
+    assert_eq!(list_int_to_obj(vec![120, 120, 120, 120]), output);
+    assert!(steps < plain_steps);
+    assert_eq!(MemoStats { hits: 3, misses: 1 }, code.memo_stats());
+}
+
+#[test]
+fn memo_is_bounded() {
+    let func = parse(lex("oqlcq"));
+    let input = list_int_to_obj(vec![5, 3, 6, 2, 4, 1, 5, 3]);
+    let roomy = compile_memoized(&func, 100);
+    let cramped = compile_memoized(&func, 1);
+    let expected = list_int_to_obj(vec![1, 2, 3, 3, 4, 5, 5, 6]);
+    assert_eq!(expected, roomy.run(input.clone()));
+    assert_eq!(expected, cramped.run(input));
+    assert_eq!(6, roomy.memo_stats().misses);
+    assert!(cramped.memo_stats().misses > 6);
+}
+
+fn run_threaded(program: &str, input: &Object, threads: usize) -> Object {
+    let func = parse(lex(program));
+    crate::parallel::set_threads(threads);
+    let (output, _) = execute_with_limit(&compile(&func), input.clone(), Some(100_000));
+    crate::parallel::set_threads(1);
+    output
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let input = list_int_to_obj((0..200).map(|i| (i * 37) % 101).collect());
+    for program in [
+        "mqlcq", "fql", "fqsq", "oqnq", "oqlq", "gqlq", "gqsq", "lfqlq", "hfqnq",
+    ] {
+        let sequential = run_threaded(program, &input, 1);
+        let parallel = run_threaded(program, &input, 4);
+        assert_eq!(sequential, parallel, "{}", program);
+    }
+}
+
+#[test]
+fn parallel_leaves_results_lazy() {
+    // Forty lists of 20! permutations, which only the length of is wanted.
+    let input = list_int_to_obj(vec![20; 40]);
+    let sequential = run_threaded("lmc", &input, 1);
+    assert_eq!(int_to_obj(40), sequential);
+    assert_eq!(sequential, run_threaded("lmc", &input, 4));
+}
+
+#[test]
+fn parallel_first_error() {
+    let mut elems: Vec<Object> = (0..200).map(|_| int_to_obj(0)).collect();
+    elems[100] = Object::from_str("1/2");
+    elems[150] = list_int_to_obj(vec![]);
+    let input = List(elems.clone().into());
+    let fraction = Error("Power set of a fraction".to_string());
+    assert_eq!(fraction, run_threaded("mqyhq", &input, 4));
+    elems.reverse();
+    let head = Error("Head of empty list".to_string());
+    assert_eq!(head, run_threaded("mqyhq", &List(elems.into()), 4));
+}
+
+#[test]
+fn parallel_step_limit() {
+    let func = parse(lex("mqxhq"));
+    let input = list_int_to_obj((0..100).collect());
+    crate::parallel::set_threads(4);
+    let (output, steps) = execute_with_limit(&compile(&func), input, Some(1000));
+    crate::parallel::set_threads(1);
+    assert_eq!(Error(OUT_OF_STEPS.to_string()), output);
+    assert!(steps > 1000);
+}
+
+#[test]
+fn bench_corpus_parses() {
+    for (name, program, _) in crate::bench::CORPUS {
+        assert!(try_lex(program).and_then(try_parse).is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn bench_measures() {
+    let measurement = crate::bench::measure("permutations", "c", "3", 3);
+    let (_, steps) = execute_with_limit(&compile(&parse(lex("c"))), int_to_obj(3), None);
+    assert_eq!(steps, measurement.steps);
+    // Six permutations of three ints, and the list holding them.
+    assert_eq!(25, measurement.peak);
+    let path = std::env::temp_dir().join(format!("minipyth-bench-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    crate::bench::save(&[measurement], path).unwrap();
+    let saved = crate::bench::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    let bigger = crate::bench::measure("permutations", "c", "4", 1);
+    let report = crate::bench::report(&[bigger], Some(&saved));
+    assert!(report.contains("steps were"), "{}", report);
+    assert!(report.contains("peak was 25"), "{}", report);
+}
+
+#[test]
+fn check_marks_inverses() {
+    let program = "miqihhq";
+    let marked = crate::check::mark_inverses(parse(lex(program)), program);
+    let desired = Func::Bound(vec![Func::Higher(
+        HigherFunc::Map,
+        Box::new(Func::Higher(
+            HigherFunc::CheckedInverse(1),
+            Box::new(Func::Bound(vec![
+                Func::Higher(
+                    HigherFunc::CheckedInverse(3),
+                    Box::new(Func::Basic(BasicFunc::Head)),
+                ),
+                Func::Basic(BasicFunc::Head),
+            ])),
+        )),
+    )]);
+    assert_eq!(desired, marked);
+    let input = list_int_to_obj(vec![3, 4]);
+    assert_eq!(run_prog(program, input.clone()), marked.execute(input));
+}
+
+#[test]
+fn check_finds_mismatches() {
+    use crate::check::mismatch;
+    // The inverse of a primality test isn't a factorization.
+    let func = parse(lex("p"));
+    assert_eq!(
+        Some(List(Seq::default())),
+        mismatch(&func, &int_to_obj(1), &int_to_obj(1))
+    );
+    assert_eq!(
+        None,
+        mismatch(&func, &list_int_to_obj(vec![2, 3]), &int_to_obj(6))
+    );
+    let error = Error("Head of empty list".to_string());
+    assert_eq!(None, mismatch(&func, &int_to_obj(1), &error));
+}
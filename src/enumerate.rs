@@ -0,0 +1,286 @@
+use crate::number::Num;
+use crate::{out_of_steps, take_step, Object};
+
+use num_rational::BigRational;
+use num_traits::Signed;
+
+// Which objects an enumeration gives. Lists are made from every kind either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kinds {
+    All,
+    Ints,
+    Rationals,
+    Lists,
+}
+
+impl Kinds {
+    fn admits(self, obj: &Object) -> bool {
+        match self {
+            Kinds::All => true,
+            Kinds::Ints => matches!(obj, Object::Int(_)),
+            Kinds::Rationals => matches!(obj, Object::Rational(_)),
+            Kinds::Lists => matches!(obj, Object::List(_)),
+        }
+    }
+}
+
+// Every number and list of numbers and lists, smallest first, each exactly once.
+// Zero has size 0, and other ints have their number of binary digits as their size,
+// so 1 and -1 have size 1, 2, -2, 3 and -3 have size 2 and so on.
+// A rational in lowest terms has the digits of its numerator and denominator as its size,
+// so 1/2 and -1/2 have size 3, and come after the ints of that size.
+// A list is one bigger than its elements, each plus one. Hints all have size one.
+// Every object has a finite size, and there are finitely many of each size,
+// so every object comes up eventually. Each object made costs a step,
+// and the enumeration ends early once out of steps.
+pub struct Objects {
+    // Every object of each size so far, with its depth.
+    by_size: Vec<Vec<(Object, usize)>>,
+    index: usize,
+    hints: Vec<Object>,
+    // Every sequence of elements of each total size so far, with its depth,
+    // where each element's size counts one extra.
+    sequences: Vec<Vec<(Vec<Object>, usize)>>,
+    // Lists nested deeper than this are left out. Ints have depth 0.
+    max_depth: Option<usize>,
+    // Objects bigger than this are left out, so the enumeration ends.
+    max_size: Option<usize>,
+    non_negative: bool,
+    kinds: Kinds,
+}
+
+// Every object, smallest first.
+pub fn objects() -> Objects {
+    Objects {
+        by_size: vec![],
+        index: 0,
+        hints: vec![],
+        sequences: vec![],
+        max_depth: None,
+        max_size: None,
+        non_negative: false,
+        kinds: Kinds::All,
+    }
+}
+
+// How deeply obj's made elements are nested.
+fn depth(obj: &Object) -> usize {
+    match obj {
+        Object::List(l) => {
+            let mut deepest = 0;
+            l.for_each_made(|elem| deepest = deepest.max(depth(elem)));
+            deepest + 1
+        }
+        _ => 0,
+    }
+}
+
+// Whether every number already made in obj is at least zero.
+fn is_non_negative(obj: &Object) -> bool {
+    match obj {
+        Object::Int(n) => !n.is_negative(),
+        Object::Rational(r) => !r.is_negative(),
+        Object::List(l) => {
+            let mut non_negative = true;
+            l.for_each_made(|elem| non_negative &= is_non_negative(elem));
+            non_negative
+        }
+        Object::Error(_) => false,
+    }
+}
+
+impl Objects {
+    // Gives these first, moving them to size one from their usual place.
+    // Hints which the other filters leave out are dropped.
+    pub fn hints(mut self, hints: Vec<Object>) -> Objects {
+        self.hints = vec![];
+        for hint in hints {
+            if !self.hints.contains(&hint) {
+                self.hints.push(hint);
+            }
+        }
+        self
+    }
+    pub fn max_depth(mut self, depth: usize) -> Objects {
+        self.max_depth = Some(depth);
+        self
+    }
+    pub fn max_size(mut self, size: usize) -> Objects {
+        self.max_size = Some(size);
+        self
+    }
+    // Only numbers and lists of numbers.
+    pub fn flat(self) -> Objects {
+        self.max_depth(1)
+    }
+    // No negative numbers, in lists or otherwise.
+    pub fn non_negative(mut self) -> Objects {
+        self.non_negative = true;
+        self
+    }
+    pub fn ints_only(mut self) -> Objects {
+        self.kinds = Kinds::Ints;
+        self
+    }
+    pub fn rationals_only(mut self) -> Objects {
+        self.kinds = Kinds::Rationals;
+        self
+    }
+    pub fn lists_only(mut self) -> Objects {
+        self.kinds = Kinds::Lists;
+        self
+    }
+    fn fits(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max| depth <= max)
+    }
+    fn fits_hint(&self, hint: &Object, depth: usize) -> bool {
+        self.fits(depth)
+            && (!self.non_negative || is_non_negative(hint))
+            && (self.kinds != Kinds::Ints || depth == 0)
+    }
+    // The positive ints with the given number of binary digits, a step each.
+    fn ints_with_digits(digits: usize) -> Vec<Num> {
+        if digits == 0 {
+            return vec![];
+        }
+        let digits = u32::try_from(digits).expect("Sizes stay small");
+        let mut ints = vec![];
+        let mut int = Num::from(2).pow(digits - 1);
+        let end = Num::from(2).pow(digits);
+        while int < end && take_step() {
+            ints.push(int.clone());
+            int += Num::from(1);
+        }
+        ints
+    }
+    // The numbers of the given size which aren't hints, and at size one the hints.
+    // Ints come first, then rationals by the size of their denominator. Ints aren't made
+    // when only rationals are wanted, nor rationals when only ints are.
+    fn atoms_of_size(&self, size: usize) -> Vec<Object> {
+        let mut atoms = vec![];
+        if self.kinds != Kinds::Rationals {
+            if size == 0 {
+                atoms.push(Object::Int(Num::from(0)));
+            }
+            for int in Objects::ints_with_digits(size) {
+                atoms.push(Object::Int(int.clone()));
+                if !self.non_negative {
+                    atoms.push(Object::Int(-int));
+                }
+            }
+        }
+        if self.kinds != Kinds::Ints {
+            for denom_digits in 2..size {
+                let numers = Objects::ints_with_digits(size - denom_digits);
+                for denom in Objects::ints_with_digits(denom_digits) {
+                    for numer in &numers {
+                        if !take_step() {
+                            return atoms;
+                        }
+                        let ratio = BigRational::new(numer.to_big(), denom.to_big());
+                        if ratio.denom() != &denom.to_big() {
+                            continue;
+                        }
+                        atoms.push(Object::Rational(ratio.clone()));
+                        if !self.non_negative {
+                            atoms.push(Object::Rational(-ratio));
+                        }
+                    }
+                }
+            }
+        }
+        atoms
+    }
+    // The atoms of the given size with their depths, and at size one the hints.
+    fn atoms_and_hints_of_size(&self, size: usize) -> Vec<(Object, usize)> {
+        let mut atoms: Vec<(Object, usize)> = self
+            .atoms_of_size(size)
+            .into_iter()
+            .map(|atom| (atom, 0))
+            .filter(|(atom, _)| !self.hints.contains(atom))
+            .collect();
+        if size == 1 {
+            atoms.extend(
+                self.hints
+                    .iter()
+                    .map(|hint| (hint.clone(), depth(hint)))
+                    .filter(|(hint, depth)| self.fits_hint(hint, *depth)),
+            );
+        }
+        atoms
+    }
+    // Every list of the given size, made from the smaller objects already known.
+    fn lists_of_size(&mut self, size: usize) -> Vec<(Object, usize)> {
+        let elems_size = size - 1;
+        if self.sequences.len() == elems_size {
+            let sequences = self.sequences_of_total(elems_size);
+            if out_of_steps() {
+                return vec![];
+            }
+            self.sequences.push(sequences);
+        }
+        self.sequences[elems_size]
+            .iter()
+            .filter(|(_, deepest)| self.fits(deepest + 1))
+            .map(|(elems, deepest)| (Object::List(elems.clone().into()), deepest + 1))
+            .filter(|(list, _)| !self.hints.contains(list))
+            .collect()
+    }
+    // Every sequence of objects whose sizes, each plus one, add up to total,
+    // with the depth of the deepest, from the smaller totals already known.
+    // Stops early once out of steps.
+    fn sequences_of_total(&self, total: usize) -> Vec<(Vec<Object>, usize)> {
+        if total == 0 {
+            return vec![(vec![], 0)];
+        }
+        let mut sequences = vec![];
+        for first_size in 0..total {
+            let rests = &self.sequences[total - first_size - 1];
+            for (first, first_depth) in &self.by_size[first_size] {
+                for (rest, rest_depth) in rests {
+                    if !take_step() {
+                        return sequences;
+                    }
+                    let mut sequence = vec![first.clone()];
+                    sequence.extend(rest.iter().cloned());
+                    sequences.push((sequence, *first_depth.max(rest_depth)));
+                }
+            }
+        }
+        sequences
+    }
+}
+
+impl Iterator for Objects {
+    type Item = Object;
+    fn next(&mut self) -> Option<Object> {
+        loop {
+            if out_of_steps() {
+                return None;
+            }
+            if let Some(current) = self.by_size.last() {
+                while self.index < current.len() {
+                    self.index += 1;
+                    let obj = &current[self.index - 1].0;
+                    if self.kinds.admits(obj) {
+                        return Some(obj.clone());
+                    }
+                }
+            }
+            let size = self.by_size.len();
+            if self.max_size.is_some_and(|max| size > max) {
+                return None;
+            }
+            let mut next = self.atoms_and_hints_of_size(size);
+            if size > 0 && matches!(self.kinds, Kinds::All | Kinds::Lists) {
+                next.extend(self.lists_of_size(size));
+            }
+            // A size cut short is made again next time, so that nothing is skipped.
+            if out_of_steps() {
+                return None;
+            }
+            self.by_size.push(next);
+            self.index = 0;
+        }
+    }
+}
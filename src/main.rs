@@ -1,10 +1,17 @@
-use clap::{App, Arg};
-use num_bigint::{BigInt, ToBigInt};
+use clap::{App, AppSettings, Arg, SubCommand};
+use num_rational::BigRational;
 use num_traits::cast::ToPrimitive;
 use num_traits::{One, Signed, Zero};
 
-use std::collections::{HashMap, HashSet};
+use number::Num;
+use seq::Seq;
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
+use std::io::Write;
+use std::panic::RefUnwindSafe;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Func {
@@ -12,14 +19,35 @@ enum Func {
     Higher(HigherFunc, Box<Func>),
     Double(DoubleFunc, Box<Func>, Box<Func>),
     Bound(Vec<Func>),
+    // A precomputed result, which ignores the input.
+    Literal(Object),
 }
 impl Func {
+    // If func maps every int x to sign * x + offset, returns sign and offset.
+    fn as_affine(&self) -> Option<(i64, Num)> {
+        match self {
+            Func::Basic(BasicFunc::Head) => Some((1, One::one())),
+            Func::Basic(BasicFunc::Tail) => Some((1, -Num::one())),
+            Func::Basic(BasicFunc::Negate) => Some((-1, Zero::zero())),
+            Func::Bound(funcs) => funcs.iter().rev().try_fold(
+                (1, Zero::zero()),
+                |(sign, offset): (i64, Num), func| {
+                    let (outer_sign, outer_offset) = func.as_affine()?;
+                    Some((sign * outer_sign, offset * outer_sign + outer_offset))
+                },
+            ),
+            _ => None,
+        }
+    }
     fn execute(&self, arg: Object) -> Object {
         use Func::*;
-        match self {
+        if !take_step() {
+            return Object::Error(OUT_OF_STEPS.to_string());
+        }
+        let result = match self {
             Basic(basic) => basic.execute(arg),
-            Higher(higher_func, func) => higher_func.execute(func, arg),
-            Double(double_func, func1, func2) => double_func.execute(func1, func2, arg),
+            Higher(higher_func, func) => higher_func.execute(&**func, arg),
+            Double(double_func, func1, func2) => double_func.execute(&**func1, &**func2, arg),
             Bound(funcs) => {
                 let mut working_obj = arg;
                 for func in funcs.iter().rev() {
@@ -27,37 +55,198 @@ impl Func {
                 }
                 working_obj
             }
+            Literal(object) => object.clone(),
+        };
+        // A list cut short reads as empty, so whatever was made from it is wrong.
+        if out_of_steps() {
+            Object::Error(OUT_OF_STEPS.to_string())
+        } else {
+            result
         }
     }
+    // Undoes the func directly, or if some part of it has no direct inverse for its
+    // argument, searches for an input to it. Other errors, such as `it` on [], are kept.
+    // Only the func as a whole is searched for, so that how a sequence is nested,
+    // or whether it has just one function, doesn't change the result.
     fn inverse_execute(&self, arg: Object) -> Object {
+        match self.invert(arg.clone()) {
+            Object::Error(e) if e == NO_INVERSE => search::preimage(self, &arg),
+            _ if out_of_steps() => Object::Error(OUT_OF_STEPS.to_string()),
+            inverted => inverted,
+        }
+    }
+    fn invert(&self, arg: Object) -> Object {
         use Func::*;
         match self {
             Basic(basic) => basic.inverse_execute(arg),
-            Higher(higher_func, func) => higher_func.inverse_execute(func, arg),
-            Double(double_func, func1, func2) => double_func.inverse_execute(func1, func2, arg),
-            Bound(funcs) => {
-                let mut working_obj = arg;
-                for func in funcs.iter().rev() {
-                    working_obj = func.inverse_execute(working_obj);
+            Higher(higher_func, func) => higher_func.inverse_execute(&**func, arg),
+            Double(double_func, func1, func2) => {
+                double_func.inverse_execute(&**func1, &**func2, arg)
+            }
+            // Undoes the last function run first, so runs the inverses front to back.
+            Bound(funcs) => funcs
+                .iter()
+                .fold(arg, |working_obj, func| func.invert(working_obj)),
+            // Every input gives the constant, so the first one searched does.
+            Literal(object) => {
+                if matches!(arg, Object::Error(_)) {
+                    arg
+                } else if &arg == object {
+                    Object::Int(Zero::zero())
+                } else {
+                    Object::Error("Inverse of a precomputed constant".to_string())
                 }
-                working_obj
             }
         }
     }
 }
 
+// Anything a higher-order function can call: a Func, or its compiled form.
+trait Callee: Clone + Send + Sync + RefUnwindSafe + 'static {
+    fn execute(&self, arg: Object) -> Object;
+    fn inverse_execute(&self, arg: Object) -> Object;
+    fn as_affine(&self) -> Option<(i64, Num)>;
+    // The function which runs this one's inverse.
+    fn inverse(&self) -> Self;
+}
+
+impl Callee for Func {
+    fn execute(&self, arg: Object) -> Object {
+        Func::execute(self, arg)
+    }
+    fn inverse_execute(&self, arg: Object) -> Object {
+        Func::inverse_execute(self, arg)
+    }
+    fn as_affine(&self) -> Option<(i64, Num)> {
+        Func::as_affine(self)
+    }
+    fn inverse(&self) -> Func {
+        Func::Higher(HigherFunc::Inverse, Box::new(self.clone()))
+    }
+}
+
+const OUT_OF_STEPS: &str = "Step limit exceeded";
+const TOO_LONG: &str = "List too long to hold in memory";
+// Given by an inverse that can't be worked out directly, so is searched for instead.
+const NO_INVERSE: &str = "No direct inverse";
+
+fn too_long() -> Object {
+    Object::Error(TOO_LONG.to_string())
+}
+
+thread_local! {
+    static STEPS: Cell<u64> = const { Cell::new(0) };
+    static STEP_LIMIT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Counts steps of evaluation. Returns false once the step limit has been passed.
+fn take_steps(count: u64) -> bool {
+    let steps = STEPS.with(|s| {
+        s.set(s.get().saturating_add(count));
+        s.get()
+    });
+    STEP_LIMIT.with(|l| l.get().is_none_or(|limit| steps <= limit))
+}
+
+fn take_step() -> bool {
+    take_steps(1)
+}
+
+fn out_of_steps() -> bool {
+    let steps = STEPS.with(|s| s.get());
+    STEP_LIMIT.with(|l| l.get().is_some_and(|limit| steps > limit))
+}
+
+// Restores the enclosing step count and limit, even if evaluation panics.
+struct StepGuard(u64, Option<u64>);
+
+impl Drop for StepGuard {
+    fn drop(&mut self) {
+        STEPS.with(|s| s.set(self.0));
+        STEP_LIMIT.with(|l| l.set(self.1));
+    }
+}
+
+// Runs func with a fresh step count, giving up once limit steps have been taken.
+// Returns the result and the number of steps used.
+fn execute_with_limit<F: Callee>(func: &F, arg: Object, limit: Option<u64>) -> (Object, u64) {
+    let (result, steps) = counting_steps(limit, || {
+        let result = func.execute(arg);
+        result.force();
+        if out_of_steps() {
+            Object::Error(OUT_OF_STEPS.to_string())
+        } else {
+            result
+        }
+    });
+    (result, steps)
+}
+
+// Runs work with a fresh step count and the given limit, returning the steps it took.
+fn counting_steps<T>(limit: Option<u64>, work: impl FnOnce() -> T) -> (T, u64) {
+    let _guard = StepGuard(STEPS.with(|s| s.get()), STEP_LIMIT.with(|l| l.get()));
+    STEPS.with(|s| s.set(0));
+    STEP_LIMIT.with(|l| l.set(limit));
+    let result = work();
+    (result, STEPS.with(|s| s.get()))
+}
+
+// Hashing walks the whole of obj, and shared lists can be far larger than the steps
+// it took to build them, so hashing costs a step per list, paid for here first.
+// Returns false once out of steps.
+fn take_hash_steps(obj: &Object) -> bool {
+    match obj {
+        Object::List(l) => take_step() && l.iter().all(take_hash_steps),
+        _ => !out_of_steps(),
+    }
+}
+
+// How many more steps can be taken before the limit, if there is one.
+fn steps_remaining() -> Option<u64> {
+    let steps = STEPS.with(|s| s.get());
+    STEP_LIMIT.with(|l| l.get().map(|limit| limit.saturating_sub(steps)))
+}
+
+// A rational standing for an int, as an index, a count or the end of a range,
+// has its magnitude rounded up, so 5/2 stands for 3 and -5/2 for -3.
+fn round_up_magnitude(r: &BigRational) -> Num {
+    let magnitude = Num::from(r.abs().ceil().to_integer());
+    if r.is_negative() {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Object {
-    Int(BigInt),
-    List(Vec<Object>),
+    Int(Num),
+    // Never a whole number, which is an Int instead.
+    Rational(BigRational),
+    List(Seq),
     Error(String),
 }
 
+// Numbers come before lists, and lists before errors. Ints and rationals are ordered
+// together by value, lists lexicographically, and errors by message.
+impl Ord for Object {
+    fn cmp(&self, other: &Object) -> Ordering {
+        self.compare(other, true)
+    }
+}
+
+impl PartialOrd for Object {
+    fn partial_cmp(&self, other: &Object) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Object::*;
         match self {
             Int(i) => write!(f, "{}", i),
+            Rational(r) => write!(f, "{}", r),
             List(l) => {
                 write!(f, "[")?;
                 for (index, elem) in l.iter().enumerate() {
@@ -77,9 +266,13 @@ impl Object {
     fn from_str(string: &str) -> Object {
         use Object::*;
         if string.is_empty() {
-            return List(vec![]);
+            return List(Seq::default());
         }
         if !string.contains('[') && !string.contains(',') {
+            if string.contains('/') {
+                let ratio = string.parse().expect("Nonlist should be a fraction");
+                return Object::from_ratio(ratio);
+            }
             let integer = string.parse().expect("Nonlist should be int");
             return Int(integer);
         }
@@ -103,7 +296,7 @@ impl Object {
                 let sub_elem = Object::from_str(trimmed);
                 sub_vec.push(sub_elem);
             }
-            List(sub_vec)
+            List(sub_vec.into())
         } else {
             let mut cursor = 0;
             let mut sub_vec = vec![];
@@ -112,8 +305,7 @@ impl Object {
                     .chars()
                     .enumerate()
                     .skip(cursor)
-                    .filter(|(_, c)| *c == '[')
-                    .next();
+                    .find(|(_, c)| *c == '[');
                 if let Some((bracket_index, _)) = next_bracket {
                     let inner = sub_string[cursor..bracket_index].trim();
                     let inner_obj = Object::from_str(inner);
@@ -137,8 +329,7 @@ impl Object {
                     .chars()
                     .enumerate()
                     .skip(cursor)
-                    .filter(|(_, c)| *c == ']')
-                    .next()
+                    .find(|(_, c)| *c == ']')
                     .expect("Open has close")
                     .0;
                 let inner = sub_string[cursor..=next_close].trim();
@@ -146,72 +337,94 @@ impl Object {
                 sub_vec.push(inner_obj);
                 cursor = next_close + 1;
             }
-            List(sub_vec)
+            List(sub_vec.into())
         }
     }
 }
 
-#[derive(PartialOrd, Ord, PartialEq, Eq, Hash, Clone)]
-struct SortKey(bool, BigInt, Vec<SortKey>);
-
 impl Object {
-    fn to_key(&self) -> SortKey {
+    // The order sort and group keys are compared in: like cmp, but all errors are alike,
+    // so that sorting and grouping treat them the same whatever their messages.
+    fn key_cmp(&self, other: &Object) -> Ordering {
+        self.compare(other, false)
+    }
+    fn compare(&self, other: &Object, errors_by_message: bool) -> Ordering {
         use Object::*;
+        match (self, other) {
+            (Int(a), Int(b)) => a.cmp(b),
+            (Rational(a), Rational(b)) => a.cmp(b),
+            (Int(a), Rational(b)) => a.to_ratio().cmp(b),
+            (Rational(a), Int(b)) => a.cmp(&b.to_ratio()),
+            (List(a), List(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| a.compare(b, errors_by_message))
+                .find(|order| order.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (Error(a), Error(b)) if errors_by_message => a.cmp(b),
+            (Error(_), Error(_)) => Ordering::Equal,
+            (Int(_) | Rational(_), _) | (List(_), Error(_)) => Ordering::Less,
+            (List(_), Int(_) | Rational(_)) | (Error(_), _) => Ordering::Greater,
+        }
+    }
+    // A number, which is an Int if it's whole.
+    fn from_ratio(r: BigRational) -> Object {
+        if r.is_integer() {
+            Object::Int(Num::from(r.to_integer()))
+        } else {
+            Object::Rational(r)
+        }
+    }
+    // The value of a number, as a fraction.
+    fn as_ratio(&self) -> Option<BigRational> {
         match self {
-            Int(i) => SortKey(false, i.clone(), vec![]),
-            List(l) => SortKey(
-                true,
-                Zero::zero(),
-                l.iter().map(|obj| obj.to_key()).collect(),
-            ),
-            Error(_) => SortKey(true, One::one(), vec![]),
+            Object::Int(i) => Some(i.to_ratio()),
+            Object::Rational(r) => Some(r.clone()),
+            Object::List(_) | Object::Error(_) => None,
+        }
+    }
+    // Makes every lazy element, so that the work is done under the current step limit.
+    fn force(&self) {
+        if let Object::List(l) = self {
+            l.iter().for_each(Object::force);
         }
     }
     fn is_truthy(&self) -> bool {
         use Object::*;
         match self {
-            Int(i) => *i != Zero::zero(),
+            Int(i) => !i.is_zero(),
+            Rational(_) => true,
             List(l) => !l.is_empty(),
             Error(_) => false,
         }
     }
-    fn to_list(self) -> Vec<Object> {
+    // Ints become the lazy range [0, i), or its reverse for negative i.
+    // Rationals become the ints in the same range, so 5/2 is [0, 1, 2].
+    #[allow(clippy::wrong_self_convention)]
+    fn to_seq(self) -> Seq {
         use Object::*;
         match self {
-            Int(i) if i < Zero::zero() => {
-                let mut nums = vec![];
-                let mut j: BigInt = Zero::zero();
-                let target = -i;
-                loop {
-                    if &j == &target {
-                        break;
-                    }
-                    nums.push(Int(j.clone()));
-                    j += 1;
-                }
-                nums.reverse();
-                nums
-            }
-            Int(i) => {
-                let mut nums = vec![];
-                let mut j: BigInt = Zero::zero();
-                loop {
-                    if &j == &i {
-                        break;
-                    }
-                    nums.push(Int(j.clone()));
-                    j += 1;
-                }
-                nums
-            }
+            Int(i) => Seq::upto(i),
+            Rational(r) => Seq::upto(round_up_magnitude(&r)),
             List(l) => l,
             a @ Error(_) => panic!("to_list called on {:?}", a),
         }
     }
+    #[allow(clippy::wrong_self_convention)]
+    fn to_list(self) -> Vec<Object> {
+        self.to_seq().into_vec()
+    }
+    // The elements, if this is a list.
+    fn as_list(&self) -> Option<Vec<Object>> {
+        match self {
+            Object::List(l) => Some(l.to_vec()),
+            Object::Int(_) | Object::Rational(_) | Object::Error(_) => None,
+        }
+    }
     fn to_pretty(&self, length_cap: usize) -> String {
         use Object::*;
         match self {
-            Int(_) => format!("{}", self),
+            Int(_) | Rational(_) => format!("{}", self),
             List(l) => {
                 let basic = format!("{}", self);
                 if basic.len() <= length_cap || length_cap <= 40 {
@@ -265,67 +478,69 @@ impl BasicFunc {
         use Object::*;
         match (self, arg) {
             (Head, Int(i)) => Int(i + 1),
-            (Head, List(mut l)) => {
-                if l.is_empty() {
-                    Error("Head of empty list".to_string())
-                } else {
-                    l.remove(0)
-                }
-            }
+            (Head, Rational(r)) => Rational(r + BigRational::one()),
+            (Head, List(l)) => match l.front() {
+                Some(first) => first,
+                None => Error("Head of empty list".to_string()),
+            },
             (Tail, Int(i)) => Int(i - 1),
-            (Tail, List(mut l)) => {
+            (Tail, Rational(r)) => Rational(r - BigRational::one()),
+            (Tail, List(l)) => {
                 if l.is_empty() {
                     Error("Tail of empty list".to_string())
                 } else {
-                    l.remove(0);
-                    List(l)
+                    List(l.tail())
                 }
             }
             (Sum, Int(i)) => {
-                if i == Zero::zero() {
+                if i.is_zero() {
                     Int(One::one())
                 } else {
                     Int(Zero::zero())
                 }
             }
+            // A rational is never zero.
+            (Sum, Rational(_)) => Int(Zero::zero()),
             (Sum, List(l)) => {
-                if l.iter().all(|elem| matches!(elem, Int(_))) {
+                if let Some(total) = l.range_sum() {
+                    Int(total)
+                } else if l.iter().all(|elem| matches!(elem, Int(_))) {
                     let total = l
                         .iter()
                         .map(|elem| if let Int(i) = elem { i } else { unreachable!() })
                         .sum();
                     Int(total)
+                } else if let Some(values) = BasicFunc::numbers(&l) {
+                    Object::from_ratio(values.into_iter().sum())
                 } else {
                     let mut output = vec![];
                     for elem in l {
                         match elem {
-                            Int(_) | Error(_) => output.push(elem),
+                            Int(_) | Rational(_) | Error(_) => output.push(elem),
                             List(l) => output.extend(l),
                         }
                     }
-                    List(output)
+                    List(output.into())
                 }
             }
-            (Product, Int(i)) => {
-                let a = i.abs();
-                if a < 2.to_bigint().unwrap() {
-                    List(vec![])
-                } else {
-                    let mut factors = vec![];
-                    let mut j = 2.to_bigint().unwrap();
-                    let mut work = a;
-                    while &j * &j <= work {
-                        if &work % &j == Zero::zero() {
-                            work /= &j;
-                            factors.push(j.to_bigint().unwrap());
-                        } else {
-                            j += 1;
-                        }
-                    }
-                    if work > One::one() {
-                        factors.push(work)
+            (Product, Int(i)) => match primes::factorize(&i) {
+                Some(factors) => List(factors.into_iter().map(Int).collect()),
+                None => Error(OUT_OF_STEPS.to_string()),
+            },
+            // The prime factors of the numerator, after one over each factor of the
+            // denominator, so that the product is the rational again.
+            (Product, Rational(r)) => {
+                let numer = primes::factorize(&Num::from(r.numer().clone()));
+                let denom = primes::factorize(&Num::from(r.denom().clone()));
+                match (numer, denom) {
+                    (Some(numer), Some(denom)) => {
+                        let inverses = denom
+                            .into_iter()
+                            .rev()
+                            .map(|d| Rational(BigRational::new(One::one(), d.to_big())));
+                        List(inverses.chain(numer.into_iter().map(Int)).collect())
                     }
-                    List(factors.into_iter().map(Int).collect())
+                    _ => Error(OUT_OF_STEPS.to_string()),
                 }
             }
             (Product, List(l)) => {
@@ -335,16 +550,21 @@ impl BasicFunc {
                         .map(|elem| if let Int(i) = elem { i } else { unreachable!() })
                         .product();
                     Int(total)
+                } else if let Some(values) = BasicFunc::numbers(&l) {
+                    Object::from_ratio(values.into_iter().product())
                 } else if l.iter().any(|elem| matches!(elem, Error(_))) {
                     panic!("Product has error in list: {:?}", l);
                 } else {
                     let list_of_lists: Vec<Vec<Object>> =
                         l.into_iter().map(|elem| elem.to_list()).collect();
-                    let mut staged_lists = vec![vec![]];
-                    for sub_list in list_of_lists {
+                    let mut staged_lists: Vec<Vec<Object>> = vec![vec![]];
+                    for sub_list in &list_of_lists {
                         let mut next_stage = vec![];
-                        for old_list in staged_lists {
-                            for elem in sub_list.clone() {
+                        for old_list in &staged_lists {
+                            if !take_step() {
+                                return Error(OUT_OF_STEPS.to_string());
+                            }
+                            for elem in sub_list {
                                 let mut new_list = old_list.clone();
                                 new_list.push(elem.clone());
                                 next_stage.push(new_list);
@@ -352,109 +572,91 @@ impl BasicFunc {
                         }
                         staged_lists = next_stage;
                     }
-                    List(staged_lists.into_iter().map(List).collect())
+                    List(
+                        staged_lists
+                            .into_iter()
+                            .map(|list| List(list.into()))
+                            .collect(),
+                    )
                 }
             }
             (Combine, List(l)) => {
-                if let Some(first_error) = l.iter().filter(|elem| matches!(elem, Error(_))).next() {
+                if let Some(first_error) = l.iter().find(|elem| matches!(elem, Error(_))) {
                     first_error.clone()
                 } else if l.is_empty() {
-                    List(vec![])
+                    List(Seq::default())
                 } else {
                     let longest = l
                         .iter()
                         .map(|elem| match elem {
-                            Int(_) => 1,
+                            Int(_) | Rational(_) => Some(1),
                             List(inner) => inner.len(),
                             Error(_) => unreachable!("No errors"),
                         })
-                        .max()
-                        .expect("Empty -> 1");
+                        .collect::<Option<Vec<usize>>>()
+                        .and_then(|lens| lens.into_iter().max());
+                    let Some(longest) = longest else {
+                        return too_long();
+                    };
                     let mut output = vec![];
                     for index in 0..longest {
                         let mut row = vec![];
-                        for elem in &l {
+                        for elem in l.iter() {
                             let maybe_to_push = match elem {
-                                a @ Int(_) => {
+                                a @ (Int(_) | Rational(_)) => {
                                     if index == 0 {
                                         Some(a.clone())
                                     } else {
                                         None
                                     }
                                 }
-                                List(inner) => inner.get(index).cloned(),
+                                List(inner) => inner.get(index),
                                 Error(_) => unreachable!("No errors"),
                             };
                             if let Some(to_push) = maybe_to_push {
                                 row.push(to_push)
                             };
                         }
-                        output.push(List(row))
+                        output.push(List(row.into()))
                     }
-                    List(output)
+                    List(output.into())
                 }
             }
             (Combine, Int(i)) => {
-                let base = Int(i).to_list();
-                let mut perms = vec![(vec![], base.clone())];
-                for _ in 0..base.len() {
-                    let mut new_perms = vec![];
-                    for (part_perm, rem) in perms {
-                        for i in 0..rem.len() {
-                            let mut new_rem = rem.clone();
-                            let extract = new_rem.remove(i);
-                            let mut new_part = part_perm.clone();
-                            new_part.push(extract);
-                            new_perms.push((new_part, new_rem));
-                        }
-                    }
-                    perms = new_perms;
-                }
-                List(perms.into_iter().map(|(perm, _)| List(perm)).collect())
+                combinatorics::permutations(Seq::upto(i)).map_or_else(too_long, List)
+            }
+            (Combine, arg @ Rational(_)) => {
+                combinatorics::permutations(arg.to_seq()).map_or_else(too_long, List)
             }
             (PowerSet, Int(i)) => {
-                if i < Zero::zero() {
-                    // Rationals
-                    Error("Negative exponent in power set".to_string())
-                } else {
-                    Int(2
-                        .to_bigint()
-                        .unwrap()
-                        .pow(i.to_u64().expect("Exponent small") as u32))
+                let exponent = i.abs().to_u64().expect("Exponent small");
+                // Each word of output costs a step.
+                if !take_steps(exponent / 64) {
+                    return Error(OUT_OF_STEPS.to_string());
                 }
-            }
-            (PowerSet, List(l)) => {
-                let num_subsets = 2u64.pow(l.len() as u32);
-                let mut output = vec![];
-                for i in 0..num_subsets {
-                    let mut subset = vec![];
-                    for (index, elem) in l.iter().enumerate() {
-                        let mask = 1 << index;
-                        if i & mask > 0 {
-                            subset.push(elem.clone());
-                        }
-                    }
-                    output.push(List(subset))
+                let power = Num::from(2).pow(exponent as u32);
+                if i.is_negative() {
+                    Rational(BigRational::new(One::one(), power.to_big()))
+                } else {
+                    Int(power)
                 }
-                List(output)
-            }
-            (Length, List(l)) => Int(l.len().to_bigint().unwrap()),
-            (Length, Int(i)) => {
-                let (_sign, bits) = i.to_radix_be(2);
-                List(
-                    bits.iter()
-                        .map(|&b| Int((b as i64).to_bigint().unwrap()))
-                        .collect(),
-                )
             }
+            (PowerSet, Rational(_)) => Error("Power set of a fraction".to_string()),
+            (PowerSet, List(l)) => combinatorics::subsets(l).map_or_else(too_long, List),
+            (Length, List(l)) => Int(l.count()),
+            (Length, Rational(_)) => Error("Length of a fraction".to_string()),
+            (Length, Int(i)) => List(
+                i.bits()
+                    .into_iter()
+                    .map(|b| Int((b as i64).into()))
+                    .collect(),
+            ),
             (Negate, Int(i)) => Int(-i),
-            (Negate, List(mut l)) => {
-                l.reverse();
-                List(l)
-            }
-            (Equal, List(mut l)) => {
-                if let Some(last) = l.pop() {
-                    let same = l.iter().all(|elem| elem == &last);
+            (Negate, Rational(r)) => Rational(-r),
+            (Negate, List(l)) => List(l.reversed()),
+            (Equal, List(l)) => {
+                if let Some((last, rest)) = l.split_last() {
+                    let same = rest.iter().all(|elem| elem == last);
                     if same {
                         Int(One::one())
                     } else {
@@ -464,39 +666,22 @@ impl BasicFunc {
                     Int(One::one())
                 }
             }
-            (Equal, Int(i)) => {
-                let base = Int(i).to_list();
-                let mut perms = vec![(vec![], base.clone())];
-                let mut out = vec![vec![]];
-                for _ in 0..base.len() {
-                    let mut new_perms = vec![];
-                    for (part_perm, rem) in perms {
-                        for i in 0..rem.len() {
-                            let mut new_rem = rem.clone();
-                            let extract = new_rem.remove(i);
-                            let mut new_part = part_perm.clone();
-                            new_part.push(extract);
-                            new_perms.push((new_part.clone(), new_rem));
-                            out.push(new_part);
-                        }
-                    }
-                    perms = new_perms;
-                }
-                List(out.into_iter().map(List).collect())
+            (Equal, Int(i)) => combinatorics::arrangements(Seq::upto(i)).map_or_else(|e| e, List),
+            (Equal, arg @ Rational(_)) => {
+                combinatorics::arrangements(arg.to_seq()).map_or_else(|e| e, List)
             }
             (AllPair, List(l)) => {
-                if l.len() >= 2 && l.iter().skip(1).any(|elem| matches!(elem, List(_))) {
+                let at_least_two = l.len().is_none_or(|len| len >= 2);
+                if at_least_two && l.iter().skip(1).any(|elem| matches!(elem, List(_))) {
                     let (first, rest) = l.split_first().expect("Checked 2");
-                    let rest_lists: Vec<Vec<Object>> = rest
-                        .into_iter()
-                        .map(|elem| elem.clone().to_list())
-                        .collect();
+                    let rest_lists: Vec<Vec<Object>> =
+                        rest.iter().map(|elem| elem.clone().to_list()).collect();
                     let out: Vec<Object> = rest_lists
                         .into_iter()
                         .map(|list| {
                             let paired = list
                                 .into_iter()
-                                .map(|elem| List(vec![first.clone(), elem]))
+                                .map(|elem| List(vec![first.clone(), elem].into()))
                                 .collect();
                             List(paired)
                         })
@@ -504,10 +689,10 @@ impl BasicFunc {
                     if out.len() == 1 {
                         out[0].clone()
                     } else {
-                        List(out)
+                        List(out.into())
                     }
-                } else if l.len() >= 2 && matches!(l[0], List(_)) {
-                    let mut rest = l.clone();
+                } else if at_least_two && matches!(l.first(), Some(List(_))) {
+                    let mut rest = l.clone().into_vec();
                     let second = rest.remove(1);
                     let rest_lists: Vec<Vec<Object>> = rest
                         .into_iter()
@@ -518,7 +703,7 @@ impl BasicFunc {
                         .map(|list| {
                             let paired = list
                                 .into_iter()
-                                .map(|elem| List(vec![elem, second.clone()]))
+                                .map(|elem| List(vec![elem, second.clone()].into()))
                                 .collect();
                             List(paired)
                         })
@@ -526,21 +711,21 @@ impl BasicFunc {
                     if out.len() == 1 {
                         out[0].clone()
                     } else {
-                        List(out)
+                        List(out.into())
                     }
                 } else {
                     List(
                         l.iter()
-                            .map(|elem| List(vec![List(l.clone()), elem.clone()]))
+                            .map(|elem| List(vec![List(l.clone()), elem.clone()].into()))
                             .collect(),
                     )
                 }
             }
-            (AllPair, arg @ Int(_)) => {
+            (AllPair, arg @ (Int(_) | Rational(_))) => {
                 let list = arg.clone().to_list();
                 List(
                     list.into_iter()
-                        .map(|elem| List(vec![arg.clone(), elem]))
+                        .map(|elem| List(vec![arg.clone(), elem].into()))
                         .collect(),
                 )
             }
@@ -549,94 +734,140 @@ impl BasicFunc {
                 let mut seen = HashSet::new();
                 let mut out = vec![];
                 for elem in l {
+                    if !take_hash_steps(&elem) {
+                        return Error(OUT_OF_STEPS.to_string());
+                    }
                     if !seen.contains(&elem) {
                         seen.insert(elem.clone());
                         out.push(elem);
                     }
                 }
-                List(out)
+                List(out.into())
             }
             (Deduplicate, Int(i)) => {
-                if i == Zero::zero() {
-                    List(vec![])
-                } else {
-                    let base = Int(i).to_list();
-                    let mut partitions = vec![];
-                    let num_options = 2u64.pow((base.len() - 1) as u32);
-                    for split in 0..num_options {
-                        let mut part = vec![vec![base[0].clone()]];
-                        for split_pos in 0..(base.len() - 1) {
-                            let mask = 1 << split_pos;
-                            if mask & split > 0 {
-                                part.push(vec![]);
-                            }
-                            let part_len = part.len() - 1;
-                            part[part_len].push(base[split_pos + 1].clone());
-                        }
-                        partitions.push(List(part.into_iter().map(List).collect()));
-                    }
-                    List(partitions)
-                }
+                combinatorics::partitions(Seq::upto(i)).map_or_else(too_long, List)
+            }
+            (Deduplicate, arg @ Rational(_)) => {
+                combinatorics::partitions(arg.to_seq()).map_or_else(too_long, List)
             }
             (_, a @ Error(_)) => a,
         }
     }
+    // Some input which execute maps to arg, or an error if there is none.
     fn inverse_execute(&self, arg: Object) -> Object {
         use BasicFunc::*;
         use Object::*;
         match (self, arg) {
+            (_, a @ Error(_)) => a,
             (Head, Int(i)) => Int(i - 1),
-            (Head, List(mut l)) => {
+            (Head, Rational(r)) => Rational(r - BigRational::one()),
+            (Head, List(l)) => match l.back() {
+                Some(last) => last,
+                None => Error("End (inverse head) of empty list".to_string()),
+            },
+            (Tail, Int(i)) => Int(i + 1),
+            (Tail, Rational(r)) => Rational(r + BigRational::one()),
+            (Tail, List(l)) => {
                 if l.is_empty() {
-                    Error("End (inverse head) of empty list".to_string())
+                    Error("Inverse tail of empty list".to_string())
                 } else {
-                    l.remove(l.len() - 1)
+                    List(l.init())
                 }
             }
-            (Tail, List(mut l)) => {
-                if l.is_empty() {
-                    Error("Inverse tail of empty list".to_string())
+            (Sum, arg) => List(vec![arg].into()),
+            // Every pair of ints is divided exactly, so ipp only undoes factorization
+            // of numbers with more or fewer than two prime factors.
+            (Product, List(l)) if matches!(&*l, [Int(_), Int(_)]) => {
+                let (num, den) = match &*l {
+                    [Int(num), Int(den)] => (num, den),
+                    _ => unreachable!("Checked ints"),
+                };
+                if den.is_zero() {
+                    Error("Divide by zero".to_string())
                 } else {
-                    l.pop();
-                    List(l)
+                    Object::from_ratio(BigRational::new(num.to_big(), den.to_big()))
                 }
             }
-            (Product, List(l)) if l.len() == 2 => {
-                if let Int(num) = &l[0] {
-                    if let Int(den) = &l[1] {
-                        let zero: BigInt = Zero::zero();
-                        if den == &zero {
-                            return Error("Divide by zero".to_string());
-                        } else {
-                            return List(vec![Int(num / den), Int(num % den)]);
+            // Undoes factorization.
+            (Product, List(l)) if l.iter().all(|elem| matches!(elem, Int(_))) => Int(l
+                .iter()
+                .map(|elem| if let Int(i) = elem { i } else { unreachable!() })
+                .product()),
+            (Product, List(l)) if l.iter().all(|elem| matches!(elem, Int(_) | Rational(_))) => {
+                let values = BasicFunc::numbers(&l).expect("Checked numbers");
+                Object::from_ratio(values.into_iter().product())
+            }
+            // Undoes the Cartesian product: each factor is the distinct values in its column.
+            (Product, List(l)) => {
+                let rows: Vec<Vec<Object>> = match l.iter().map(|row| row.as_list()).collect() {
+                    Some(rows) => rows,
+                    None => return Error("Inverse product of a mix of ints and lists".to_string()),
+                };
+                let width = rows.first().map_or(0, |row| row.len());
+                if rows.iter().any(|row| row.len() != width) {
+                    return Error("Inverse product of lists of different lengths".to_string());
+                }
+                let columns = (0..width).map(|index| {
+                    let mut column: Vec<Object> = vec![];
+                    for row in &rows {
+                        if !column.contains(&row[index]) {
+                            column.push(row[index].clone());
                         }
                     }
+                    List(column.into())
+                });
+                List(columns.collect())
+            }
+            (Product, Int(i)) => match primes::is_prime(&i) {
+                Some(true) => Int(One::one()),
+                Some(false) => Int(Zero::zero()),
+                None => Error(OUT_OF_STEPS.to_string()),
+            },
+            // No fraction is prime.
+            (Product, Rational(_)) => Int(Zero::zero()),
+            (PowerSet, Int(i)) => {
+                if i < 1 {
+                    Error("Inverse power set of a non-positive int".to_string())
+                } else {
+                    // Rounds down, when i isn't a power of 2.
+                    Int(Num::from(i.bits().len() - 1))
                 }
-                panic!("Unimplemented inverse product: {:?} {:?}", self, List(l));
             }
-            (Product, Int(i)) => {
-                if i <= One::one() {
-                    Int(Zero::zero())
+            (PowerSet, Rational(r)) => {
+                if r.is_negative() {
+                    Error("Inverse power set of a negative fraction".to_string())
                 } else {
-                    let mut div = 2;
-                    let mut is_prime = true;
-                    while &(div * div).to_bigint().unwrap() <= &i {
-                        if &i % div == Zero::zero() {
-                            is_prime = false;
-                            break;
+                    Int(BasicFunc::floor_log2(&r))
+                }
+            }
+            // The union of the subsets, in order of appearance.
+            (PowerSet, List(l)) => {
+                let mut union: Vec<Object> = vec![];
+                for subset in l.iter() {
+                    match subset.as_list() {
+                        Some(elems) => {
+                            for elem in elems {
+                                if !union.contains(&elem) {
+                                    union.push(elem);
+                                }
+                            }
                         }
-                        div += 1;
-                    }
-                    if is_prime {
-                        Int(One::one())
-                    } else {
-                        Int(Zero::zero())
+                        None => return Error("Inverse power set of a list of ints".to_string()),
                     }
                 }
+                List(union.into())
             }
+            (Length, Int(i)) => {
+                if i.is_negative() {
+                    Error("Inverse length of a negative int".to_string())
+                } else {
+                    List(Seq::upto(i))
+                }
+            }
+            (Length, Rational(_)) => Error("Inverse length of a fraction".to_string()),
             (Length, List(l)) => {
                 if l.iter().all(|elem| matches!(elem, Int(_))) {
-                    let mut total: BigInt = Zero::zero();
+                    let mut total: Num = Zero::zero();
                     for bit in l {
                         if let Int(b) = bit {
                             total *= 2;
@@ -645,12 +876,109 @@ impl BasicFunc {
                     }
                     Int(total)
                 } else {
-                    panic!("Unimplemented inverse l: {:?} {:?}", self, List(l));
+                    Error("Inverse length (from binary) of a list with lists".to_string())
                 }
             }
-            (Sum, arg) => List(vec![arg]),
-            (_, a @ Error(_)) => a,
-            (s, a) => panic!("Basic inverse func unimplemented: {:?}, {:?}", s, a),
+            (Negate, arg) => Negate.execute(arg),
+            (Equal, Int(i)) => {
+                if i == 1 {
+                    List(Seq::default())
+                } else if i.is_zero() {
+                    List(Seq::upto(Num::from(2)))
+                } else {
+                    Error("Inverse equal of an int other than 0 or 1".to_string())
+                }
+            }
+            (Equal, Rational(_)) => Error("Inverse equal of a fraction".to_string()),
+            // The arrangements of n end with the arrangement of all n.
+            (Equal, List(l)) => match l.back().as_ref().and_then(Object::as_list) {
+                Some(last) => Int(Num::from(last.len())),
+                None => Error("Inverse equal of a list not ending in a list".to_string()),
+            },
+            (Combine, Int(_)) => Error("Inverse combine of an int".to_string()),
+            (Combine, Rational(_)) => Error("Inverse combine of a fraction".to_string()),
+            // Transposing is its own inverse.
+            (Combine, arg @ List(_)) => Combine.execute(arg),
+            (AllPair, List(l)) => BasicFunc::unpair(l),
+            (AllPair, Int(_)) => Error("Inverse all-pairs of an int".to_string()),
+            (AllPair, Rational(_)) => Error("Inverse all-pairs of a fraction".to_string()),
+            (Constant, Int(i)) => {
+                if i.is_zero() {
+                    Int(i)
+                } else {
+                    Error("Inverse constant of an int other than 0".to_string())
+                }
+            }
+            (Constant, Rational(_)) => Error("Inverse constant of a fraction".to_string()),
+            (Constant, List(_)) => Error("Inverse constant of a list".to_string()),
+            // A list without repeats deduplicates to itself.
+            (Deduplicate, List(l)) => {
+                let mut seen = HashSet::new();
+                for elem in l.iter() {
+                    if !take_hash_steps(elem) {
+                        return Error(OUT_OF_STEPS.to_string());
+                    }
+                    if !seen.insert(elem) {
+                        return Error("Inverse deduplicate of a list with repeats".to_string());
+                    }
+                }
+                List(l)
+            }
+            (Deduplicate, Int(_)) => Error("Inverse deduplicate of an int".to_string()),
+            (Deduplicate, Rational(_)) => Error("Inverse deduplicate of a fraction".to_string()),
+        }
+    }
+    // The values of l's elements, if they're all numbers.
+    fn numbers(l: &Seq) -> Option<Vec<BigRational>> {
+        l.iter().map(|elem| elem.as_ratio()).collect()
+    }
+    // The largest power of 2 at most r, which is positive, as an exponent.
+    fn floor_log2(r: &BigRational) -> Num {
+        let mut exponent = r.numer().bits() as i64 - r.denom().bits() as i64;
+        let power = Num::from(2).pow(exponent.unsigned_abs() as u32).to_ratio();
+        let power = if exponent < 0 { power.recip() } else { power };
+        if r < &power {
+            exponent -= 1;
+        }
+        Num::from(exponent)
+    }
+    // Undoes all-pairs, given its list of pairs.
+    fn unpair(l: Seq) -> Object {
+        use Object::*;
+        let pairs: Option<Vec<(Object, Object)>> = l
+            .iter()
+            .map(|pair| match pair.as_list().as_deref() {
+                Some([first, second]) => Some((first.clone(), second.clone())),
+                _ => None,
+            })
+            .collect();
+        let pairs = match pairs {
+            Some(pairs) => pairs,
+            None => return Error("Inverse all-pairs of a list of non-pairs".to_string()),
+        };
+        let (firsts, seconds): (Vec<Object>, Vec<Object>) = pairs.into_iter().unzip();
+        let seconds = List(seconds.into());
+        match firsts.first() {
+            // All-pairs of 0.
+            None => Int(Zero::zero()),
+            Some(first) if firsts.iter().all(|elem| elem == first) => {
+                let first = first.clone();
+                if first == seconds
+                    || matches!(&first, Int(i) if seconds == List(Seq::upto(i.clone())))
+                {
+                    // All-pairs of an int, or a list of ints, paired with each of its own elements.
+                    first
+                } else {
+                    List(vec![first, seconds].into())
+                }
+            }
+            Some(_) => match &seconds {
+                List(l) if l.iter().all(|elem| Some(elem) == l.front().as_ref()) => {
+                    let second = l.front().expect("Nonempty");
+                    List(vec![List(firsts.into()), second].into())
+                }
+                _ => Error("Inverse all-pairs without a shared element".to_string()),
+            },
         }
     }
 }
@@ -667,6 +995,9 @@ enum HigherFunc {
     Update,
     ReverseLookup,
     DeepIndex,
+    // Inverse, warning if func doesn't map the result back to the argument.
+    // Holds the position of the i in the program.
+    CheckedInverse(usize),
 }
 impl HigherFunc {
     fn first_error(mut arg: Vec<Object>) -> Object {
@@ -674,112 +1005,152 @@ impl HigherFunc {
         if let Some(index) = maybe_index {
             arg.remove(index)
         } else {
-            Object::List(arg)
+            Object::List(arg.into())
         }
     }
-    fn execute(&self, func: &Func, arg: Object) -> Object {
+    fn execute<F: Callee>(&self, func: &F, arg: Object) -> Object {
         use HigherFunc::*;
         use Object::*;
         match self {
             Map => {
-                let list = arg.to_list();
-                let out_list = list.into_iter().map(|obj| func.execute(obj)).collect();
+                let seq = arg.to_seq();
+                if let Some((sign, offset)) = func.as_affine() {
+                    if let Some(mapped) = seq.map_affine(sign, &offset) {
+                        return List(mapped);
+                    }
+                }
+                let out_list = parallel::map(func, seq.into_vec(), parallel::threads());
                 HigherFunc::first_error(out_list)
             }
             Filter => {
-                let mut list = arg.to_list();
-                list.retain(|obj| func.execute(obj.clone()).is_truthy());
-                List(list)
+                let func = func.clone();
+                let source = arg.to_seq().iter_from(0);
+                let threads = parallel::threads();
+                if threads > 1 {
+                    return List(Seq::lazy(None, parallel::filter(func, source, threads)));
+                }
+                let kept = source.filter(move |obj| func.execute(obj.clone()).is_truthy());
+                List(Seq::lazy(None, Box::new(kept)))
             }
             Order => {
-                let mut list = arg.to_list();
-                list.sort_by_key(|obj| {
-                    let new_obj = func.execute(obj.clone());
-                    new_obj.to_key()
-                });
-                List(list)
+                let list = arg.to_list();
+                // Each key is worked out once, before sorting.
+                let keys = parallel::map(func, list.clone(), parallel::threads());
+                let mut keyed: Vec<(Object, Object)> = keys.into_iter().zip(list).collect();
+                keyed.sort_by(|(key1, _), (key2, _)| key1.key_cmp(key2));
+                List(keyed.into_iter().map(|(_, obj)| obj).collect())
             }
             FixedPoint => {
                 let mut seen = HashSet::new();
                 let mut sequence = vec![];
                 let mut current = arg;
-                while !seen.contains(&current) && !matches!(current, Error(_)) {
-                    seen.insert(current.clone());
+                while !matches!(current, Error(_)) {
+                    if !take_hash_steps(&current) {
+                        return Error(OUT_OF_STEPS.to_string());
+                    }
+                    if !seen.insert(current.clone()) {
+                        break;
+                    }
                     sequence.push(current.clone());
                     current = func.execute(current);
                 }
-                List(sequence)
+                List(sequence.into())
             }
             Inverse => func.inverse_execute(arg),
+            CheckedInverse(position) => {
+                let result = func.inverse_execute(arg.clone());
+                if let Some(back) = check::mismatch(func, &arg, &result) {
+                    eprintln!(
+                        "Warning: the inverse at {} gave {}, which maps back to {}, not {}",
+                        position, result, back, arg
+                    );
+                }
+                result
+            }
             Repeat => {
                 let (times, start) = match arg {
-                    List(mut l) => {
+                    List(l) => {
                         if l.is_empty() {
                             (List(l.clone()), List(l))
-                        } else if l.len() == 1 {
-                            (l[0].clone(), l[0].clone())
+                        } else if l.len() == Some(1) {
+                            (l.nth(&Zero::zero()), l.nth(&Zero::zero()))
                         } else {
-                            let first = l.remove(0);
-                            let second = l.remove(0);
-                            (first, second)
+                            (l.nth(&Zero::zero()), l.nth(&One::one()))
                         }
                     }
-                    Int(_) | Error(_) => (arg.clone(), arg.clone()),
+                    Int(_) | Rational(_) | Error(_) => (arg.clone(), arg.clone()),
+                };
+                let times = match times {
+                    Rational(r) => Int(round_up_magnitude(&r)),
+                    times => times,
                 };
                 match times {
                     List(l) => {
+                        let times = l.count();
                         let mut output = vec![start.clone()];
                         let mut current = start;
-                        for _ in 0..l.len() {
+                        let mut j: Num = Zero::zero();
+                        while j < times && take_step() {
                             current = func.execute(current);
                             output.push(current.clone());
+                            j += 1;
                         }
-                        List(output)
+                        List(output.into())
                     }
                     Int(i) => {
-                        if i < Zero::zero() {
-                            List(vec![])
+                        if i.is_negative() {
+                            List(Seq::default())
                         } else {
                             let mut output = vec![];
                             let mut current = start;
-                            let mut j: BigInt = Zero::zero();
-                            while j < i {
+                            let mut j: Num = Zero::zero();
+                            while j < i && take_step() {
                                 current = func.execute(current);
                                 output.push(current.clone());
                                 j += 1;
                             }
-                            List(output)
+                            List(output.into())
                         }
                     }
-                    Error(_) => List(vec![]),
+                    Rational(_) | Error(_) => List(Seq::default()),
                 }
             }
+            // Sorts by key, keeping elements with alike keys in order, then splits
+            // wherever the key changes.
             GroupBy => {
                 let list = arg.to_list();
-                let mut groups = HashMap::new();
-                for elem in list {
-                    let key = func.execute(elem.clone()).to_key();
-                    let group = groups.entry(key).or_insert(vec![]);
-                    group.push(elem);
+                let keys = parallel::map(func, list.clone(), parallel::threads());
+                let mut keyed: Vec<(Object, Object)> = keys.into_iter().zip(list).collect();
+                keyed.sort_by(|(key1, _), (key2, _)| key1.key_cmp(key2));
+                let mut groups: Vec<(Object, Vec<Object>)> = vec![];
+                for (key, elem) in keyed {
+                    match groups.last_mut() {
+                        Some((last, group)) if last.key_cmp(&key).is_eq() => group.push(elem),
+                        _ => groups.push((key, vec![elem])),
+                    }
                 }
-                let mut key_vals: Vec<(SortKey, Vec<Object>)> = groups.into_iter().collect();
-                key_vals.sort_by_key(|(s, _)| s.clone());
-                let vals = key_vals.into_iter().map(|(_, v)| List(v)).collect();
-                List(vals)
+                List(
+                    groups
+                        .into_iter()
+                        .map(|(_, group)| List(group.into()))
+                        .collect(),
+                )
             }
             Update => {
                 let list = arg.to_list();
                 if list.is_empty() {
-                    List(list)
+                    List(list.into())
                 } else {
                     let (first, rest) = list.split_first().unwrap();
                     let update_ints = match first {
                         Int(i) => vec![i.clone()],
+                        Rational(r) => vec![round_up_magnitude(r)],
                         List(l) => l
-                            .into_iter()
+                            .iter()
                             .map(|elem| match elem {
                                 Int(i) => i.clone(),
-                                List(list) => list.len().to_bigint().unwrap(),
+                                Rational(r) => round_up_magnitude(r),
+                                List(list) => list.count(),
                                 Error(_) => Zero::zero(),
                             })
                             .collect(),
@@ -791,90 +1162,49 @@ impl HigherFunc {
                             let mut list = elem.clone().to_list();
                             if !list.is_empty() {
                                 for int in &update_ints {
-                                    let big_index = ((int % list.len()) + list.len()) % list.len();
+                                    let len = Num::from(list.len());
+                                    let big_index = ((int % &len) + &len) % &len;
                                     let index: usize =
                                         big_index.to_usize().expect("big_index positive");
                                     let new = func.execute(list[index].clone());
                                     list[index] = new;
                                 }
                             }
-                            List(list)
+                            List(list.into())
                         })
                         .collect();
                     if updated_lists.len() == 1 {
                         updated_lists[0].clone()
                     } else {
-                        List(updated_lists)
-                    }
-                }
-            }
-            ReverseLookup => {
-                let mut combos: Vec<(i64, u32, i64)> = vec![(2, 2, 9)];
-                let mut output = None;
-                'outer: for i in 0.. {
-                    let pos_arg = Int(i.to_bigint().unwrap());
-                    if func.execute(pos_arg.clone()) == arg {
-                        output = Some(pos_arg);
-                        break;
-                    }
-                    let neg_arg = Int((-i).to_bigint().unwrap());
-                    if func.execute(neg_arg.clone()) == arg {
-                        output = Some(neg_arg);
-                        break;
-                    }
-                    if (combos[0].2) <= i {
-                        let (options, length, score) = combos.remove(0);
-                        for (new_options, new_length) in
-                            vec![(options + 1, length), (options, length + 1)]
-                        {
-                            let new_total = (new_options * 2 - 1).pow(new_length);
-                            combos.push((new_options, new_length, new_total));
-                        }
-                        combos.sort_by_key(|c| c.2);
-                        // TODO: factorize for higher depth lists.
-                        let base = options * 2 - 1;
-                        for index in 0..score {
-                            let digits: Vec<i64> = (0..length)
-                                .scan(index, |index, _| {
-                                    let digit = *index % base;
-                                    *index /= base;
-                                    Some((options - 1) - digit)
-                                })
-                                .collect();
-                            let digit_arg = List(
-                                digits
-                                    .into_iter()
-                                    .map(|digit| Int(digit.to_bigint().unwrap()))
-                                    .collect(),
-                            );
-                            if func.execute(digit_arg.clone()) == arg {
-                                output = Some(digit_arg);
-                                break 'outer;
-                            }
-                        }
+                        List(updated_lists.into())
                     }
                 }
-                output.expect("Initialized in loop")
             }
-            DeepIndex => {
-                if let List(list) = arg {
-                    let structure = func.execute(Int(list.len().to_bigint().unwrap()));
-                    let result = HigherFunc::deep_index(structure, list);
-                    result
-                } else {
-                    panic!("DeepIndex non-list arg unimplemented");
+            ReverseLookup => search::lookup(func, &arg),
+            // An int is indexed as the range it stands for, like map and filter do.
+            DeepIndex => match arg {
+                a @ Error(_) => a,
+                arg => {
+                    let list = arg.to_seq();
+                    let structure = func.execute(Int(list.count()));
+                    HigherFunc::deep_index(structure, &list)
                 }
-            }
+            },
         }
     }
-    fn inverse_execute(&self, func: &Func, arg: Object) -> Object {
+    fn inverse_execute<F: Callee>(&self, func: &F, arg: Object) -> Object {
         use HigherFunc::*;
         use Object::*;
+        // An earlier inverse in a sequence failed, which nothing here can undo.
+        if let Error(_) = arg {
+            return arg;
+        }
         match self {
             Order => {
                 let list = arg.to_list();
+                let keys: Vec<Object> = list.iter().map(|obj| func.execute(obj.clone())).collect();
                 let mut indices: Vec<usize> = (0..list.len()).collect();
-                indices.sort_by_key(|&i| func.execute(list[i].clone()).to_key());
+                indices.sort_by(|&i, &j| keys[i].key_cmp(&keys[j]));
                 let mut inverse_indices: Vec<Option<usize>> = vec![None; list.len()];
                 for (index, &perm) in indices.iter().enumerate() {
                     inverse_indices[perm] = Some(index);
@@ -885,32 +1215,149 @@ impl HigherFunc {
                     .collect();
                 List(reordered)
             }
-            Inverse => func.execute(arg),
-            _ => {
-                let inv = Func::Higher(HigherFunc::Inverse, Box::new(func.clone()));
-                self.execute(&inv, arg)
-            }
+            Inverse | CheckedInverse(_) => func.execute(arg),
+            // Puts each element back where it was indexed from, assuming the input was as
+            // long as the output. Every position has to be indexed exactly once.
+            DeepIndex => match arg {
+                List(l) if l.is_empty() => List(l),
+                List(l) => {
+                    let structure = func.execute(Int(l.count()));
+                    let Some(len) = l.len() else {
+                        return too_long();
+                    };
+                    let mut slots = vec![None; len];
+                    if let Err(e) = HigherFunc::deep_place(structure, List(l), &mut slots) {
+                        return e;
+                    }
+                    match slots.into_iter().collect::<Option<Seq>>() {
+                        Some(elems) => List(elems),
+                        None => Error("Inverse deep index that misses a position".to_string()),
+                    }
+                }
+                // Any number index picks the only element of a list of one.
+                num @ (Int(_) | Rational(_)) => match func.execute(Int(1.into())) {
+                    Int(_) | Rational(_) => List(vec![num].into()),
+                    _ => Error(NO_INVERSE.to_string()),
+                },
+                a @ Error(_) => a,
+            },
+            // Any order of the elements groups the same way.
+            GroupBy => match arg {
+                List(groups) => BasicFunc::Sum.execute(List(groups)),
+                Int(_) => Error("Inverse group by of an int".to_string()),
+                Rational(_) => Error("Inverse group by of a fraction".to_string()),
+                a @ Error(_) => a,
+            },
+            // The value the iteration settled on.
+            FixedPoint => match arg {
+                List(l) => match l.back() {
+                    Some(last) => last,
+                    None => Error("Inverse fixed point of empty list".to_string()),
+                },
+                Int(_) => Error("Inverse fixed point of an int".to_string()),
+                Rational(_) => Error("Inverse fixed point of a fraction".to_string()),
+                a @ Error(_) => a,
+            },
+            // Repeating len times from the inverse of the first result gives the list back.
+            // When that start is len itself, the int alone does too.
+            Repeat => match arg {
+                List(l) => {
+                    let times = Int(l.count());
+                    let start = match l.front() {
+                        Some(first) => func.inverse_execute(first),
+                        None => return times,
+                    };
+                    if let Error(_) = start {
+                        return start;
+                    }
+                    if start == times {
+                        times
+                    } else {
+                        List(vec![times, start].into())
+                    }
+                }
+                Int(_) => Error("Inverse repeat of an int".to_string()),
+                Rational(_) => Error("Inverse repeat of a fraction".to_string()),
+                a @ Error(_) => a,
+            },
+            // The indices don't survive in the output, so this updates every position once,
+            // from the inverse of what ended up there.
+            Update => match arg {
+                List(l) => {
+                    let mut elems = Vec::new();
+                    for elem in l.iter() {
+                        match func.inverse_execute(elem.clone()) {
+                            e @ Error(_) => return e,
+                            elem => elems.push(elem),
+                        }
+                    }
+                    List(vec![List(Seq::upto(l.count())), List(elems.into())].into())
+                }
+                Int(_) => Error("Inverse update of an int".to_string()),
+                Rational(_) => Error("Inverse update of a fraction".to_string()),
+                a @ Error(_) => a,
+            },
+            ReverseLookup => func.execute(arg),
+            _ => self.execute(&func.inverse(), arg),
         }
     }
-    fn deep_index(structure: Object, list: Vec<Object>) -> Object {
+    fn deep_index(structure: Object, list: &Seq) -> Object {
         use Object::*;
         match structure {
             Int(i) => {
-                let index = (((i % list.len()) + list.len()) % list.len())
-                    .to_usize()
-                    .expect("positive");
-                list[index].clone()
+                let len = list.count();
+                if len.is_zero() {
+                    return Error("Deep index into empty list".to_string());
+                }
+                let index = ((i % &len) + &len) % &len;
+                list.nth(&index)
             }
+            Rational(r) => HigherFunc::deep_index(Int(round_up_magnitude(&r)), list),
             List(l) => {
                 let mapped = l
                     .into_iter()
-                    .map(|elem| HigherFunc::deep_index(elem, list.clone()))
+                    .map(|elem| HigherFunc::deep_index(elem, list))
                     .collect();
                 List(mapped)
             }
             Error(_) => structure,
         }
     }
+    // Undoes deep_index, putting each part of output in the slot its index names.
+    fn deep_place(
+        structure: Object,
+        output: Object,
+        slots: &mut [Option<Object>],
+    ) -> Result<(), Object> {
+        use Object::*;
+        match (structure, output) {
+            (Error(e), _) | (_, Error(e)) => Err(Error(e)),
+            (Rational(r), output) => {
+                HigherFunc::deep_place(Int(round_up_magnitude(&r)), output, slots)
+            }
+            (Int(i), output) => {
+                let len = Num::from(slots.len());
+                let index = ((i % &len) + &len) % &len;
+                let slot = &mut slots[index.to_usize().expect("Index below a usize")];
+                match slot {
+                    Some(placed) if placed != &output => Err(Error(
+                        "Inverse deep index with two elements for one position".to_string(),
+                    )),
+                    _ => {
+                        *slot = Some(output);
+                        Ok(())
+                    }
+                }
+            }
+            (List(indices), List(parts)) if indices.count() == parts.count() => indices
+                .into_iter()
+                .zip(parts)
+                .try_for_each(|(index, part)| HigherFunc::deep_place(index, part, slots)),
+            _ => Err(Error(
+                "Inverse deep index of an object shaped unlike the indices".to_string(),
+            )),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -920,7 +1367,7 @@ enum DoubleFunc {
 }
 
 impl DoubleFunc {
-    fn execute(&self, func1: &Func, func2: &Func, arg: Object) -> Object {
+    fn execute<F: Callee>(&self, func1: &F, func2: &F, arg: Object) -> Object {
         use DoubleFunc::*;
         use Object::*;
         match self {
@@ -931,6 +1378,9 @@ impl DoubleFunc {
                     if matches!(working_arg, Error(_)) {
                         break;
                     }
+                    if !take_step() {
+                        return Error(OUT_OF_STEPS.to_string());
+                    }
                     sequence.push(working_arg.clone());
                     let test = func1.execute(working_arg.clone());
                     if !test.is_truthy() {
@@ -938,7 +1388,7 @@ impl DoubleFunc {
                     }
                     working_arg = func2.execute(working_arg);
                 }
-                List(sequence)
+                List(sequence.into())
             }
             Bifurcate => {
                 let ret1 = func1.execute(arg.clone());
@@ -948,18 +1398,46 @@ impl DoubleFunc {
                 } else if matches! {ret2, Error(_)} {
                     ret2
                 } else {
-                    List(vec![ret1, ret2])
+                    List(vec![ret1, ret2].into())
                 }
             }
         }
     }
-    fn inverse_execute(&self, func1: &Func, func2: &Func, arg: Object) -> Object {
-        match self {
-            _ => {
-                let inv1 = Func::Higher(HigherFunc::Inverse, Box::new(func1.clone()));
-                let inv2 = Func::Higher(HigherFunc::Inverse, Box::new(func2.clone()));
-                self.execute(&inv1, &inv2, arg)
+    fn inverse_execute<F: Callee>(&self, func1: &F, func2: &F, arg: Object) -> Object {
+        use DoubleFunc::*;
+        use Object::*;
+        match (self, arg) {
+            (_, a @ Error(_)) => a,
+            // Each half of the pair is inverted by its own function,
+            // and they have to agree on where they came from.
+            (Bifurcate, List(l)) => {
+                if l.count() != 2 {
+                    return Error("Inverse bifurcate of a list that isn't a pair".to_string());
+                }
+                let start1 = func1.inverse_execute(l.nth(&Zero::zero()));
+                let start2 = func2.inverse_execute(l.nth(&One::one()));
+                if matches!(start1, Error(_)) {
+                    start1
+                } else if matches!(start2, Error(_)) {
+                    start2
+                } else if start1 == start2 {
+                    start1
+                } else {
+                    Error(format!(
+                        "Inverse bifurcate halves disagree: {} and {}",
+                        start1, start2
+                    ))
+                }
             }
+            (Bifurcate, Int(_)) => Error("Inverse bifurcate of an int".to_string()),
+            (Bifurcate, Rational(_)) => Error("Inverse bifurcate of a fraction".to_string()),
+            // The sequence starts with the input.
+            (While, List(l)) => match l.front() {
+                Some(first) => first,
+                None => Error("Inverse while of empty list".to_string()),
+            },
+            (While, Int(_)) => Error("Inverse while of an int".to_string()),
+            (While, Rational(_)) => Error("Inverse while of a fraction".to_string()),
         }
     }
 }
@@ -971,7 +1449,7 @@ enum BoundToken {
     SoloQuote,
 }
 #[derive(Debug)]
-enum HOF {
+enum Hof {
     Higher(HigherFunc),
     Double(DoubleFunc),
     DoubleHalf(DoubleFunc, Func),
@@ -979,44 +1457,69 @@ enum HOF {
     Quote,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    UnknownChar(usize, char),
+    BindReachedFront,
+    BindReachedQuote,
+    SoloQuoteUnbound,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            UnknownChar(index, c) => write!(f, "Unknown character {:?} at {}", c, index),
+            BindReachedFront => write!(f, "z found no higher-order function to bind to"),
+            BindReachedQuote => write!(f, "z reached an unpaired q"),
+            SoloQuoteUnbound => write!(f, "Unpaired q has no preceding higher-order function"),
+        }
+    }
+}
+
+#[cfg(test)]
 fn parse(tokens: Vec<Token>) -> Func {
-    let mut state: Vec<HOF> = vec![];
+    try_parse(tokens).unwrap_or_else(|e| panic!("Parse error: {}", e))
+}
+
+fn try_parse(tokens: Vec<Token>) -> Result<Func, ParseError> {
+    let mut state: Vec<Hof> = vec![];
     for token in tokens {
         if let Token::Bound(BoundToken::SoloQuote) = &token {
-            assert!(state.iter().all(|elem| !matches!(elem, HOF::Quote)));
+            assert!(state.iter().all(|elem| !matches!(elem, Hof::Quote)));
             let maybe_first_unbound_index =
-                state.iter().position(|elem| !matches!(elem, HOF::Func(_)));
+                state.iter().position(|elem| !matches!(elem, Hof::Func(_)));
             if let Some(first_unbound_index) = maybe_first_unbound_index {
-                state.insert(first_unbound_index + 1, HOF::Quote)
+                state.insert(first_unbound_index + 1, Hof::Quote)
             } else {
-                panic!("SoloQuote has no preceeding unbound: {:?}", state);
+                return Err(ParseError::SoloQuoteUnbound);
             }
         }
         match token {
-            Token::Basic(basic_func) => state.push(HOF::Func(Func::Basic(basic_func))),
-            Token::Higher(higher_func) => state.push(HOF::Higher(higher_func)),
-            Token::Double(double_func) => state.push(HOF::Double(double_func)),
+            Token::Basic(basic_func) => state.push(Hof::Func(Func::Basic(basic_func))),
+            Token::Higher(higher_func) => state.push(Hof::Higher(higher_func)),
+            Token::Double(double_func) => state.push(Hof::Double(double_func)),
             Token::Bound(BoundToken::Bound1) => {
                 let mut rev_bind_group = vec![];
                 loop {
                     let last = state.pop();
                     match last {
-                        None => panic!("Bind reached front"),
-                        Some(HOF::Higher(higher_func)) => {
+                        None => return Err(ParseError::BindReachedFront),
+                        Some(Hof::Higher(higher_func)) => {
                             rev_bind_group.reverse();
                             let bound_func = Func::Bound(rev_bind_group);
                             let new_func = Func::Higher(higher_func, Box::new(bound_func));
-                            state.push(HOF::Func(new_func));
+                            state.push(Hof::Func(new_func));
                             break;
                         }
-                        Some(HOF::Double(double_func)) => {
+                        Some(Hof::Double(double_func)) => {
                             rev_bind_group.reverse();
                             let bound_func = Func::Bound(rev_bind_group);
-                            let new_token = HOF::DoubleHalf(double_func, bound_func);
+                            let new_token = Hof::DoubleHalf(double_func, bound_func);
                             state.push(new_token);
                             break;
                         }
-                        Some(HOF::DoubleHalf(double_func, bound_func)) => {
+                        Some(Hof::DoubleHalf(double_func, bound_func)) => {
                             rev_bind_group.reverse();
                             let bound_func2 = Func::Bound(rev_bind_group);
                             let new_func = Func::Double(
@@ -1024,85 +1527,85 @@ fn parse(tokens: Vec<Token>) -> Func {
                                 Box::new(bound_func),
                                 Box::new(bound_func2),
                             );
-                            state.push(HOF::Func(new_func));
+                            state.push(Hof::Func(new_func));
                             break;
                         }
-                        Some(HOF::Func(func)) => rev_bind_group.push(func),
-                        Some(HOF::Quote) => panic!("Bind reached quote"),
+                        Some(Hof::Func(func)) => rev_bind_group.push(func),
+                        Some(Hof::Quote) => return Err(ParseError::BindReachedQuote),
                     }
                 }
             }
             Token::Bound(BoundToken::BoundQuote | BoundToken::SoloQuote) => {
-                let quote_count = state.iter().filter(|hof| matches!(hof, HOF::Quote)).count();
+                let quote_count = state.iter().filter(|hof| matches!(hof, Hof::Quote)).count();
                 assert!(quote_count <= 1);
                 if quote_count == 0 {
-                    state.push(HOF::Quote);
+                    state.push(Hof::Quote);
                 } else {
                     let mut rev_bind_group = vec![];
                     loop {
                         let last = state.pop();
                         match last {
                             None => unreachable!("Didn't find paired quote"),
-                            Some(HOF::Func(func)) => rev_bind_group.push(func),
-                            Some(HOF::Quote) => {
+                            Some(Hof::Func(func)) => rev_bind_group.push(func),
+                            Some(Hof::Quote) => {
                                 rev_bind_group.reverse();
                                 let bound_func = Func::Bound(rev_bind_group);
                                 let last_state = state.pop();
                                 match last_state {
-                                    Some(HOF::Higher(higher_func)) => {
+                                    Some(Hof::Higher(higher_func)) => {
                                         let new_func =
                                             Func::Higher(higher_func, Box::new(bound_func));
-                                        state.push(HOF::Func(new_func));
+                                        state.push(Hof::Func(new_func));
                                         break;
                                     }
-                                    Some(HOF::Double(double_func)) => {
-                                        state.push(HOF::DoubleHalf(double_func, bound_func));
+                                    Some(Hof::Double(double_func)) => {
+                                        state.push(Hof::DoubleHalf(double_func, bound_func));
                                         break;
                                     }
-                                    Some(HOF::DoubleHalf(double_func, old_func)) => {
+                                    Some(Hof::DoubleHalf(double_func, old_func)) => {
                                         let new_func = Func::Double(
                                             double_func,
                                             Box::new(old_func),
                                             Box::new(bound_func),
                                         );
-                                        state.push(HOF::Func(new_func));
+                                        state.push(Hof::Func(new_func));
                                         break;
                                     }
-                                    Some(HOF::Func(func)) => {
+                                    Some(Hof::Func(func)) => {
                                         let second_last_state = state.pop();
                                         match second_last_state {
-                                            Some(HOF::Double(double_func)) => {
+                                            Some(Hof::Double(double_func)) => {
                                                 let new_func = Func::Double(
                                                     double_func,
                                                     Box::new(func),
                                                     Box::new(bound_func),
                                                 );
-                                                state.push(HOF::Func(new_func));
+                                                state.push(Hof::Func(new_func));
                                                 break;
                                             }
                                             Some(elem) => {
                                                 state.push(elem);
-                                                state.push(HOF::Func(func));
-                                                state.push(HOF::Func(bound_func));
+                                                state.push(Hof::Func(func));
+                                                state.push(Hof::Func(bound_func));
                                                 break;
                                             }
                                             None => {
-                                                state.push(HOF::Func(func));
-                                                state.push(HOF::Func(bound_func));
+                                                state.push(Hof::Func(func));
+                                                state.push(Hof::Func(bound_func));
                                                 break;
                                             }
                                         }
                                     }
-                                    Some(HOF::Quote) => {
+                                    Some(Hof::Quote) => {
                                         unreachable!("Quotes eliminated left to right");
                                     }
                                     None => {
-                                        state.push(HOF::Func(bound_func));
+                                        state.push(Hof::Func(bound_func));
                                         break;
                                     }
                                 }
                             }
-                            Some(HOF::Higher(higher_func)) => {
+                            Some(Hof::Higher(higher_func)) => {
                                 let prev_func = rev_bind_group.pop();
                                 match prev_func {
                                     None => rev_bind_group.push(Func::Higher(
@@ -1113,7 +1616,7 @@ fn parse(tokens: Vec<Token>) -> Func {
                                         .push(Func::Higher(higher_func, Box::new(prev))),
                                 }
                             }
-                            Some(HOF::Double(double_func)) => {
+                            Some(Hof::Double(double_func)) => {
                                 let prev_func = rev_bind_group.pop();
                                 match prev_func {
                                     None => rev_bind_group.push(Func::Double(
@@ -1121,10 +1624,10 @@ fn parse(tokens: Vec<Token>) -> Func {
                                         Box::new(Func::Bound(vec![])),
                                         Box::new(Func::Bound(vec![])),
                                     )),
-                                    Some(prev) => state.push(HOF::DoubleHalf(double_func, prev)),
+                                    Some(prev) => state.push(Hof::DoubleHalf(double_func, prev)),
                                 }
                             }
-                            Some(HOF::DoubleHalf(double_func, old_func)) => {
+                            Some(Hof::DoubleHalf(double_func, old_func)) => {
                                 let prev_func = rev_bind_group.pop();
                                 match prev_func {
                                     None => rev_bind_group.push(Func::Double(
@@ -1154,7 +1657,7 @@ fn parse(tokens: Vec<Token>) -> Func {
     let mut open_higher: Vec<HD> = vec![];
     for hof in state {
         match hof {
-            HOF::Func(func) => {
+            Hof::Func(func) => {
                 let mut working_func = func;
                 loop {
                     match open_higher.pop() {
@@ -1179,10 +1682,10 @@ fn parse(tokens: Vec<Token>) -> Func {
                     }
                 }
             }
-            HOF::Higher(higher_func) => open_higher.push(HD::H(higher_func)),
-            HOF::Double(double_func) => open_higher.push(HD::D(double_func)),
-            HOF::DoubleHalf(double_func, func) => open_higher.push(HD::D2(double_func, func)),
-            HOF::Quote => unreachable!("All quotes paired at start of parse"),
+            Hof::Higher(higher_func) => open_higher.push(HD::H(higher_func)),
+            Hof::Double(double_func) => open_higher.push(HD::D(double_func)),
+            Hof::DoubleHalf(double_func, func) => open_higher.push(HD::D2(double_func, func)),
+            Hof::Quote => unreachable!("All quotes paired at start of parse"),
         }
     }
     if !open_higher.is_empty() {
@@ -1203,41 +1706,48 @@ fn parse(tokens: Vec<Token>) -> Func {
         }
         funcs.push(working_func);
     }
-    Func::Bound(funcs)
+    Ok(Func::Bound(funcs))
 }
+#[cfg(test)]
 fn lex(code: &str) -> Vec<Token> {
+    try_lex(code).unwrap_or_else(|e| panic!("Lex error: {}", e))
+}
+fn try_lex(code: &str) -> Result<Vec<Token>, ParseError> {
     let mut tokens: Vec<Token> = code
         .chars()
-        .map(|c| match c {
-            'a' => Token::Basic(BasicFunc::AllPair),
-            'b' => Token::Double(DoubleFunc::Bifurcate),
-            'c' => Token::Basic(BasicFunc::Combine),
-            'd' => Token::Basic(BasicFunc::Deduplicate),
-            'e' => Token::Basic(BasicFunc::Equal),
-            'f' => Token::Higher(HigherFunc::Filter),
-            'g' => Token::Higher(HigherFunc::GroupBy),
-            'h' => Token::Basic(BasicFunc::Head),
-            'i' => Token::Higher(HigherFunc::Inverse),
-            'j' => Token::Higher(HigherFunc::DeepIndex),
-            'k' => Token::Basic(BasicFunc::Constant),
-            'l' => Token::Basic(BasicFunc::Length),
-            'm' => Token::Higher(HigherFunc::Map),
-            'n' => Token::Basic(BasicFunc::Negate),
-            'o' => Token::Higher(HigherFunc::Order),
-            'p' => Token::Basic(BasicFunc::Product),
-            'q' => Token::Bound(BoundToken::BoundQuote),
-            'r' => Token::Higher(HigherFunc::Repeat),
-            's' => Token::Basic(BasicFunc::Sum),
-            't' => Token::Basic(BasicFunc::Tail),
-            'u' => Token::Higher(HigherFunc::Update),
-            'v' => Token::Higher(HigherFunc::ReverseLookup),
-            'w' => Token::Double(DoubleFunc::While),
-            'x' => Token::Higher(HigherFunc::FixedPoint),
-            'y' => Token::Basic(BasicFunc::PowerSet),
-            'z' => Token::Bound(BoundToken::Bound1),
-            _ => unimplemented!("Lex {}", c),
+        .enumerate()
+        .map(|(index, c)| {
+            Ok(match c {
+                'a' => Token::Basic(BasicFunc::AllPair),
+                'b' => Token::Double(DoubleFunc::Bifurcate),
+                'c' => Token::Basic(BasicFunc::Combine),
+                'd' => Token::Basic(BasicFunc::Deduplicate),
+                'e' => Token::Basic(BasicFunc::Equal),
+                'f' => Token::Higher(HigherFunc::Filter),
+                'g' => Token::Higher(HigherFunc::GroupBy),
+                'h' => Token::Basic(BasicFunc::Head),
+                'i' => Token::Higher(HigherFunc::Inverse),
+                'j' => Token::Higher(HigherFunc::DeepIndex),
+                'k' => Token::Basic(BasicFunc::Constant),
+                'l' => Token::Basic(BasicFunc::Length),
+                'm' => Token::Higher(HigherFunc::Map),
+                'n' => Token::Basic(BasicFunc::Negate),
+                'o' => Token::Higher(HigherFunc::Order),
+                'p' => Token::Basic(BasicFunc::Product),
+                'q' => Token::Bound(BoundToken::BoundQuote),
+                'r' => Token::Higher(HigherFunc::Repeat),
+                's' => Token::Basic(BasicFunc::Sum),
+                't' => Token::Basic(BasicFunc::Tail),
+                'u' => Token::Higher(HigherFunc::Update),
+                'v' => Token::Higher(HigherFunc::ReverseLookup),
+                'w' => Token::Double(DoubleFunc::While),
+                'x' => Token::Higher(HigherFunc::FixedPoint),
+                'y' => Token::Basic(BasicFunc::PowerSet),
+                'z' => Token::Bound(BoundToken::Bound1),
+                _ => return Err(ParseError::UnknownChar(index, c)),
+            })
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
     let num_quote = tokens
         .iter()
         .filter(|elem| matches!(elem, Token::Bound(BoundToken::BoundQuote)))
@@ -1249,22 +1759,138 @@ fn lex(code: &str) -> Vec<Token> {
             .expect("Odd means at least one");
         tokens[solo_index] = Token::Bound(BoundToken::SoloQuote);
     }
-    tokens
+    Ok(tokens)
 }
 
-fn run(program: &str, maybe_input: Option<&str>, debug: bool, pretty: bool) -> String {
-    let tokens = lex(program);
-    let func = parse(tokens);
-    if debug {
-        println!("{:#?}", func);
-    }
+// How to run a program, as chosen by command line flags.
+struct RunOptions {
+    debug: bool,
+    pretty: bool,
+    optimized: bool,
+    show_optimized: bool,
+    differential: bool,
+    memo: bool,
+    parallel: bool,
+    preimages: bool,
+    check_inverses: bool,
+    reverse: bool,
+}
+
+// How many results each memoized function remembers.
+const MEMO_CAPACITY: usize = 1 << 16;
+
+fn run(
+    program: &str,
+    maybe_input: Option<&str>,
+    maybe_expected: Option<&str>,
+    options: &RunOptions,
+) -> String {
     let input = maybe_input.unwrap_or("0");
     let parsed_input: Object = Object::from_str(input);
-    let object = func.execute(parsed_input);
-    if !pretty {
+    let expected = maybe_expected.map(Object::from_str);
+    let mode = neighbours::Mode {
+        reverse: options.reverse,
+        optimize: options.optimized || options.show_optimized,
+    };
+    let func = match try_lex(program).and_then(try_parse) {
+        Ok(func) => func,
+        Err(e) => {
+            let suggestions = suggest::did_you_mean(
+                program,
+                &parsed_input,
+                &neighbours::Outcome::ParseError(e.clone()),
+                expected.as_ref(),
+                mode,
+            );
+            return format!("Parse error: {}\n{}", e, suggestions);
+        }
+    };
+    if options.debug {
+        println!("{:#?}", func);
+    }
+    let func = if options.check_inverses {
+        check::mark_inverses(func, program)
+    } else {
+        func
+    };
+    let forward = func.clone();
+    let func = if options.reverse {
+        func.inverse()
+    } else {
+        func
+    };
+    let func = if options.optimized || options.show_optimized {
+        optimize::optimize(func, &parsed_input)
+    } else {
+        func
+    };
+    if options.show_optimized {
+        println!("{:#?}", func);
+    }
+    if options.differential {
+        if let Some((walked, compiled)) = compile::differential(&func, &parsed_input, None) {
+            eprintln!(
+                "Warning: the tree-walker gave {}, but the compiled program gave {}",
+                walked, compiled
+            );
+        }
+    }
+    if options.parallel {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        parallel::set_threads(cores);
+    }
+    if options.preimages {
+        // Printed as they're found, since there may be infinitely many.
+        let code = compile::compile(&func);
+        let mut out = std::io::stdout().lock();
+        for preimage in search::preimages(code, parsed_input) {
+            let written = if options.pretty {
+                writeln!(out, "{}", preimage.to_pretty(80))
+            } else {
+                writeln!(out, "{}", preimage)
+            };
+            match written {
+                Ok(()) => {}
+                // Whatever was reading, such as head, has seen enough.
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+                Err(e) => panic!("Writing preimages failed: {}", e),
+            }
+        }
+        return "No more preimages found".to_string();
+    }
+    let object = if options.memo {
+        let code = compile::compile_memoized(&func, MEMO_CAPACITY);
+        let object = code.run(parsed_input.clone());
+        let stats = code.memo_stats();
+        eprintln!("Memo: {} hits, {} misses", stats.hits, stats.misses);
+        object
+    } else {
+        compile::compile(&func).run(parsed_input.clone())
+    };
+    if options.reverse && options.check_inverses {
+        if let Some(back) = check::mismatch(&forward, &parsed_input, &object) {
+            eprintln!(
+                "Warning: running in reverse gave {}, which the program maps to {}, not {}",
+                object, back, parsed_input
+            );
+        }
+    }
+    let output = if !options.pretty {
         format!("{}", object)
     } else {
         object.to_pretty(80)
+    };
+    if let Object::Error(e) = object {
+        let suggestions = suggest::did_you_mean(
+            program,
+            &parsed_input,
+            &neighbours::Outcome::RuntimeError(e),
+            expected.as_ref(),
+            mode,
+        );
+        format!("{}\n{}", output, suggestions)
+    } else {
+        output
     }
 }
 
@@ -1273,6 +1899,7 @@ fn main() {
         .version("1.0")
         .author("Isaac Grosof")
         .about("Implements the Minipyth programming language")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("PROGRAM")
                 .help("The program to run")
@@ -1291,20 +1918,186 @@ fn main() {
                 .long("pretty")
                 .help("Pretty-print the output"),
         )
+        .arg(
+            Arg::with_name("EXPECT")
+                .short("e")
+                .long("expect")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("The desired output, used to rank suggestions if the program fails"),
+        )
+        .arg(
+            Arg::with_name("OPTIMIZE")
+                .short("O")
+                .long("optimize")
+                .help("Simplifies the parse tree before running it"),
+        )
+        .arg(
+            Arg::with_name("SHOW_OPTIMIZED")
+                .long("show-optimized")
+                .help("Prints the simplified parse tree, and runs it"),
+        )
+        .arg(
+            Arg::with_name("DIFFERENTIAL")
+                .long("differential")
+                .help("Also runs the program with the tree-walking interpreter, and warns if the results differ"),
+        )
+        .arg(
+            Arg::with_name("MEMO")
+                .long("memo")
+                .help("Remembers the results of functions passed to higher-order functions, and reports how often they were reused"),
+        )
+        .arg(
+            Arg::with_name("PARALLEL")
+                .long("parallel")
+                .help("Splits long maps, filters, orders and groupings across every core"),
+        )
+        .arg(
+            Arg::with_name("PREIMAGES")
+                .long("preimages")
+                .help("Prints every input which the program maps to INPUT, smallest first, as they're found"),
+        )
+        .arg(
+            Arg::with_name("CHECK_INVERSES")
+                .long("check-inverses")
+                .help("Warns whenever an inverse gives a result which its function doesn't map back to the argument"),
+        )
+        .arg(
+            Arg::with_name("REVERSE")
+                .long("reverse")
+                .help("Runs the inverse of the program, finding an input which gives INPUT"),
+        )
+        .subcommand(
+            SubCommand::with_name("neighbours")
+                .about("Runs every single-character edit of a program, grouped by output")
+                .arg(
+                    Arg::with_name("PROGRAM")
+                        .help("The program to edit")
+                        .required(true),
+                )
+                .arg(Arg::with_name("INPUT").help("The input to provide")),
+        )
+        .subcommand(
+            SubCommand::with_name("objects")
+                .about("Prints every number and list up to a size, smallest first")
+                .arg(
+                    Arg::with_name("SIZE")
+                        .help("The largest size to print")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("FLAT")
+                        .long("flat")
+                        .help("Only numbers and lists of numbers"),
+                )
+                .arg(
+                    Arg::with_name("NON_NEGATIVE")
+                        .long("non-negative")
+                        .help("No negative numbers"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Times a corpus of programs, reporting the median time, steps and peak object size of each")
+                .arg(
+                    Arg::with_name("REPETITIONS")
+                        .short("n")
+                        .long("repetitions")
+                        .takes_value(true)
+                        .default_value("5")
+                        .help("How many times to run each program"),
+                )
+                .arg(
+                    Arg::with_name("SAVE")
+                        .long("save")
+                        .takes_value(true)
+                        .help("Saves the results to a file"),
+                )
+                .arg(
+                    Arg::with_name("COMPARE")
+                        .long("compare")
+                        .takes_value(true)
+                        .help("Compares the results to ones saved earlier"),
+                ),
+        )
         .get_matches();
+    if let Some(sub_matches) = matches.subcommand_matches("bench") {
+        let repetitions = sub_matches
+            .value_of("REPETITIONS")
+            .unwrap()
+            .parse()
+            .expect("Repetitions is a number");
+        let baseline = sub_matches
+            .value_of("COMPARE")
+            .map(|path| bench::load(path).expect("Saved results are readable"));
+        let measurements = bench::run(repetitions);
+        println!("{}", bench::report(&measurements, baseline.as_deref()));
+        if let Some(path) = sub_matches.value_of("SAVE") {
+            bench::save(&measurements, path).expect("Results are writable");
+        }
+        return;
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("objects") {
+        let size = sub_matches
+            .value_of("SIZE")
+            .unwrap()
+            .parse()
+            .expect("Size is a number");
+        let mut objects = enumerate::objects().max_size(size);
+        if sub_matches.is_present("FLAT") {
+            objects = objects.flat();
+        }
+        if sub_matches.is_present("NON_NEGATIVE") {
+            objects = objects.non_negative();
+        }
+        for obj in objects {
+            println!("{}", obj);
+        }
+        return;
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("neighbours") {
+        let program = sub_matches.value_of("PROGRAM").unwrap();
+        let input = Object::from_str(sub_matches.value_of("INPUT").unwrap_or("0"));
+        println!("{}", neighbours::neighbours(program, &input));
+        return;
+    }
     let program = matches.value_of("PROGRAM").unwrap();
-    let debug = matches.is_present("DEBUG");
     let input = matches.value_of("INPUT");
-    let pretty = matches.is_present("PRETTY");
-    let result = run(program, input, debug, pretty);
+    let expected = matches.value_of("EXPECT");
+    let options = RunOptions {
+        debug: matches.is_present("DEBUG"),
+        pretty: matches.is_present("PRETTY"),
+        optimized: matches.is_present("OPTIMIZE"),
+        show_optimized: matches.is_present("SHOW_OPTIMIZED"),
+        differential: matches.is_present("DIFFERENTIAL"),
+        memo: matches.is_present("MEMO"),
+        parallel: matches.is_present("PARALLEL"),
+        preimages: matches.is_present("PREIMAGES"),
+        check_inverses: matches.is_present("CHECK_INVERSES"),
+        reverse: matches.is_present("REVERSE"),
+    };
+    let result = run(program, input, expected, &options);
     println!("{}", result);
 }
 
+mod bench;
+mod check;
+mod combinatorics;
+mod compile;
+mod enumerate;
+mod neighbours;
+mod number;
+mod optimize;
+mod parallel;
+mod primes;
+mod search;
+mod seq;
+mod suggest;
+
 #[cfg(test)]
 mod test_helpers {
     use crate::Object::*;
     use crate::{lex, parse, Object};
-    use num_bigint::ToBigInt;
 
     pub fn run_prog(program: &str, input: Object) -> Object {
         let tokens = lex(program);
@@ -1313,7 +2106,7 @@ mod test_helpers {
     }
 
     pub fn int_to_obj(int: i64) -> Object {
-        Int(int.to_bigint().unwrap())
+        Int(int.into())
     }
 
     pub fn list_int_to_obj(ints: Vec<i64>) -> Object {
@@ -1331,5 +2124,11 @@ mod codegolf;
 #[cfg(test)]
 mod coverage_code;
 
+#[cfg(test)]
+mod coverage_number;
+
 #[cfg(test)]
 mod coverage_parse;
+
+#[cfg(test)]
+mod coverage_tools;
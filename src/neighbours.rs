@@ -0,0 +1,217 @@
+use crate::optimize::optimize;
+use crate::{execute_with_limit, try_lex, try_parse, Callee, Object, ParseError};
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::panic;
+use std::sync::Once;
+
+// Programs are only ever made of these characters.
+pub const ATOMS: &str = "abcdefghijklmnopqrstuvwxyz";
+
+// Steps allowed per edited program, so that edits which loop forever still finish.
+pub const EDIT_STEP_LIMIT: u64 = 100_000;
+
+// Outputs longer than this are cut short in group headers.
+const HEADER_LENGTH_CAP: usize = 120;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Substitute(usize, char, char),
+    Insert(usize, char),
+    Delete(usize, char),
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Edit::*;
+        match self {
+            Substitute(index, old, new) => write!(f, "sub {} {}->{}", index, old, new),
+            Insert(index, new) => write!(f, "ins {} {}", index, new),
+            Delete(index, old) => write!(f, "del {} {}", index, old),
+        }
+    }
+}
+
+impl Edit {
+    pub fn apply(&self, program: &str) -> String {
+        use Edit::*;
+        let mut chars: Vec<char> = program.chars().collect();
+        match self {
+            Substitute(index, _, new) => chars[*index] = *new,
+            Insert(index, new) => chars.insert(*index, *new),
+            Delete(index, _) => {
+                chars.remove(*index);
+            }
+        }
+        chars.into_iter().collect()
+    }
+}
+
+// Every program one substitution, insertion or deletion away from program,
+// without duplicates. Deletions come first, then substitutions, then insertions.
+pub fn single_edits(program: &str) -> Vec<(String, Edit)> {
+    let chars: Vec<char> = program.chars().collect();
+    let mut edits = vec![];
+    for (index, &old) in chars.iter().enumerate() {
+        edits.push(Edit::Delete(index, old));
+    }
+    for (index, &old) in chars.iter().enumerate() {
+        for new in ATOMS.chars().filter(|&new| new != old) {
+            edits.push(Edit::Substitute(index, old, new));
+        }
+    }
+    for index in 0..=chars.len() {
+        for new in ATOMS.chars() {
+            edits.push(Edit::Insert(index, new));
+        }
+    }
+    let mut seen = HashSet::new();
+    seen.insert(program.to_string());
+    edits
+        .into_iter()
+        .map(|edit| (edit.apply(program), edit))
+        .filter(|(edited, _)| seen.insert(edited.clone()))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Output(Object),
+    RuntimeError(String),
+    ParseError(ParseError),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Outcome::*;
+        match self {
+            Output(object) => write!(f, "{}", object),
+            RuntimeError(e) => write!(f, "Runtime error: {}", e),
+            ParseError(e) => write!(f, "Parse error: {}", e),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Panic".to_string()
+    }
+}
+
+// How a program is run, so that edits of it can be run the same way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mode {
+    pub reverse: bool,
+    pub optimize: bool,
+}
+
+// Runs program under a step limit, turning parse failures, error objects and
+// interpreter panics into outcomes rather than aborting.
+pub fn evaluate(program: &str, input: &Object, limit: u64, mode: Mode) -> Outcome {
+    let func = match try_lex(program).and_then(try_parse) {
+        Ok(func) => func,
+        Err(e) => return Outcome::ParseError(e),
+    };
+    let result = panic::catch_unwind(|| {
+        let func = if mode.reverse { func.inverse() } else { func };
+        let func = if mode.optimize {
+            optimize(func, input)
+        } else {
+            func
+        };
+        execute_with_limit(&func, input.clone(), Some(limit)).0
+    });
+    match result {
+        Ok(Object::Error(e)) => Outcome::RuntimeError(e),
+        Ok(object) => Outcome::Output(object),
+        Err(payload) => Outcome::RuntimeError(panic_message(payload)),
+    }
+}
+
+thread_local! {
+    // How many calls to quietly this thread is inside.
+    static QUIET: Cell<usize> = const { Cell::new(0) };
+}
+
+static SILENCER: Once = Once::new();
+
+// Undoes one level of quietly, even if the body panics.
+struct QuietGuard;
+
+impl Drop for QuietGuard {
+    fn drop(&mut self) {
+        QUIET.with(|q| q.set(q.get() - 1));
+    }
+}
+
+// Runs body without printing the messages of any panics caught inside it.
+// Only this thread is silenced, so searches on other threads can't unsilence it.
+pub fn quietly<T>(body: impl FnOnce() -> T) -> T {
+    SILENCER.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if QUIET.with(|q| q.get()) == 0 {
+                hook(info);
+            }
+        }));
+    });
+    QUIET.with(|q| q.set(q.get() + 1));
+    let _guard = QuietGuard;
+    body()
+}
+
+// Whether this thread is inside quietly.
+pub fn is_quiet() -> bool {
+    QUIET.with(|q| q.get()) > 0
+}
+
+// Results of every single-character edit of program on input, grouped by output.
+// Successful outputs come first, then runtime errors, then parse errors.
+pub fn neighbours(program: &str, input: &Object) -> String {
+    let (original, edited) = quietly(|| {
+        let original = evaluate(program, input, EDIT_STEP_LIMIT, Mode::default());
+        let edited: Vec<(String, Edit, Outcome)> = single_edits(program)
+            .into_iter()
+            .map(|(edited, edit)| {
+                let outcome = evaluate(&edited, input, EDIT_STEP_LIMIT, Mode::default());
+                (edited, edit, outcome)
+            })
+            .collect();
+        (original, edited)
+    });
+    let mut groups: Vec<(Outcome, Vec<(String, Edit)>)> = vec![];
+    for (edited, edit, outcome) in edited {
+        match groups.iter_mut().find(|(group, _)| group == &outcome) {
+            Some((_, members)) => members.push((edited, edit)),
+            None => groups.push((outcome, vec![(edited, edit)])),
+        }
+    }
+    groups.sort_by_key(|(outcome, _)| match outcome {
+        Outcome::Output(_) => 0,
+        Outcome::RuntimeError(_) => 1,
+        Outcome::ParseError(_) => 2,
+    });
+    let width = program.chars().count() + 1;
+    let mut lines = vec![format!("{} => {}", program, original)];
+    for (outcome, members) in groups {
+        lines.push(String::new());
+        let header = format!("{}", outcome);
+        if header.chars().count() > HEADER_LENGTH_CAP {
+            let cut: String = header.chars().take(HEADER_LENGTH_CAP).collect();
+            lines.push(format!("{}...", cut));
+        } else {
+            lines.push(header);
+        }
+        for (edited, edit) in members {
+            lines.push(format!("    {:width$} {}", edited, edit, width = width));
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,296 @@
+use num_bigint::{BigInt, ToBigInt};
+use num_rational::BigRational;
+use num_traits::cast::ToPrimitive;
+use num_traits::{One, Signed, Zero};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::{Product, Sum};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+use std::str::FromStr;
+
+// An unbounded integer, stored inline while it fits in a machine word.
+// Always normalized: a Big never holds a value that fits in an i64,
+// so the derived equality and hashing agree with numeric equality.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Num {
+    Small(i64),
+    Big(BigInt),
+}
+
+impl Num {
+    pub fn to_big(&self) -> BigInt {
+        match self {
+            Num::Small(i) => BigInt::from(*i),
+            Num::Big(i) => i.clone(),
+        }
+    }
+    pub fn abs(&self) -> Num {
+        match self {
+            Num::Small(i) => match i.checked_abs() {
+                Some(a) => Num::Small(a),
+                None => Num::from(self.to_big().abs()),
+            },
+            Num::Big(i) => Num::from(i.abs()),
+        }
+    }
+    pub fn pow(&self, exponent: u32) -> Num {
+        match self {
+            Num::Small(i) => match i.checked_pow(exponent) {
+                Some(p) => Num::Small(p),
+                None => Num::from(self.to_big().pow(exponent)),
+            },
+            Num::Big(i) => Num::from(i.pow(exponent)),
+        }
+    }
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Num::Small(i) => *i < 0,
+            Num::Big(i) => i.is_negative(),
+        }
+    }
+    pub fn to_ratio(&self) -> BigRational {
+        BigRational::from_integer(self.to_big())
+    }
+    // Binary digits of the absolute value, most significant first.
+    pub fn bits(&self) -> Vec<u8> {
+        self.to_big().to_radix_be(2).1
+    }
+}
+
+impl From<BigInt> for Num {
+    fn from(i: BigInt) -> Num {
+        match i.to_i64() {
+            Some(small) => Num::Small(small),
+            None => Num::Big(i),
+        }
+    }
+}
+
+impl From<i64> for Num {
+    fn from(i: i64) -> Num {
+        Num::Small(i)
+    }
+}
+
+impl From<i32> for Num {
+    fn from(i: i32) -> Num {
+        Num::Small(i.into())
+    }
+}
+
+impl From<usize> for Num {
+    fn from(i: usize) -> Num {
+        match i64::try_from(i) {
+            Ok(small) => Num::Small(small),
+            Err(_) => Num::Big(BigInt::from(i)),
+        }
+    }
+}
+
+impl FromStr for Num {
+    type Err = num_bigint::ParseBigIntError;
+    fn from_str(string: &str) -> Result<Num, Self::Err> {
+        match string.parse::<i64>() {
+            Ok(small) => Ok(Num::Small(small)),
+            Err(_) => string.parse::<BigInt>().map(Num::from),
+        }
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Num::Small(i) => write!(f, "{}", i),
+            Num::Big(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+impl fmt::Debug for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Ord for Num {
+    fn cmp(&self, other: &Num) -> Ordering {
+        match (self, other) {
+            (Num::Small(a), Num::Small(b)) => a.cmp(b),
+            // Normalized, so a Big is beyond every Small.
+            (Num::Small(_), Num::Big(b)) => {
+                if b.is_negative() {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            }
+            (Num::Big(a), Num::Small(_)) => {
+                if a.is_negative() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+            (Num::Big(a), Num::Big(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Num {
+    fn partial_cmp(&self, other: &Num) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq<i64> for Num {
+    fn eq(&self, other: &i64) -> bool {
+        matches!(self, Num::Small(i) if i == other)
+    }
+}
+
+impl PartialOrd<i64> for Num {
+    fn partial_cmp(&self, other: &i64) -> Option<Ordering> {
+        Some(self.cmp(&Num::Small(*other)))
+    }
+}
+
+impl ToPrimitive for Num {
+    fn to_i64(&self) -> Option<i64> {
+        match self {
+            Num::Small(i) => Some(*i),
+            Num::Big(_) => None,
+        }
+    }
+    fn to_u64(&self) -> Option<u64> {
+        match self {
+            Num::Small(i) => i.to_u64(),
+            Num::Big(i) => i.to_u64(),
+        }
+    }
+}
+
+impl ToBigInt for Num {
+    fn to_bigint(&self) -> Option<BigInt> {
+        Some(self.to_big())
+    }
+}
+
+impl Neg for Num {
+    type Output = Num;
+    fn neg(self) -> Num {
+        -&self
+    }
+}
+
+impl Neg for &Num {
+    type Output = Num;
+    fn neg(self) -> Num {
+        match self {
+            Num::Small(i) => match i.checked_neg() {
+                Some(n) => Num::Small(n),
+                None => Num::from(-self.to_big()),
+            },
+            Num::Big(i) => Num::from(-i),
+        }
+    }
+}
+
+// Implements a binary operator on every mix of owned and borrowed Nums and i64s,
+// trying the checked machine-word operation before falling back to BigInt.
+macro_rules! binary_op {
+    ($trait:ident, $method:ident, $checked:ident, $assign_trait:ident, $assign_method:ident) => {
+        impl $trait<&Num> for &Num {
+            type Output = Num;
+            fn $method(self, other: &Num) -> Num {
+                if let (Num::Small(a), Num::Small(b)) = (self, other) {
+                    if let Some(result) = a.$checked(*b) {
+                        return Num::Small(result);
+                    }
+                }
+                Num::from(self.to_big().$method(other.to_big()))
+            }
+        }
+        impl $trait<Num> for &Num {
+            type Output = Num;
+            fn $method(self, other: Num) -> Num {
+                self.$method(&other)
+            }
+        }
+        impl $trait<&Num> for Num {
+            type Output = Num;
+            fn $method(self, other: &Num) -> Num {
+                (&self).$method(other)
+            }
+        }
+        impl $trait<Num> for Num {
+            type Output = Num;
+            fn $method(self, other: Num) -> Num {
+                (&self).$method(&other)
+            }
+        }
+        impl $trait<i64> for &Num {
+            type Output = Num;
+            fn $method(self, other: i64) -> Num {
+                self.$method(&Num::Small(other))
+            }
+        }
+        impl $trait<i64> for Num {
+            type Output = Num;
+            fn $method(self, other: i64) -> Num {
+                (&self).$method(&Num::Small(other))
+            }
+        }
+        impl $assign_trait<&Num> for Num {
+            fn $assign_method(&mut self, other: &Num) {
+                *self = (&*self).$method(other);
+            }
+        }
+        impl $assign_trait<Num> for Num {
+            fn $assign_method(&mut self, other: Num) {
+                *self = (&*self).$method(&other);
+            }
+        }
+        impl $assign_trait<i64> for Num {
+            fn $assign_method(&mut self, other: i64) {
+                *self = (&*self).$method(&Num::Small(other));
+            }
+        }
+    };
+}
+
+binary_op!(Add, add, checked_add, AddAssign, add_assign);
+binary_op!(Sub, sub, checked_sub, SubAssign, sub_assign);
+binary_op!(Mul, mul, checked_mul, MulAssign, mul_assign);
+// Like BigInt, division truncates towards zero.
+binary_op!(Div, div, checked_div, DivAssign, div_assign);
+binary_op!(Rem, rem, checked_rem, RemAssign, rem_assign);
+
+impl Zero for Num {
+    fn zero() -> Num {
+        Num::Small(0)
+    }
+    fn is_zero(&self) -> bool {
+        self == &Num::Small(0)
+    }
+}
+
+impl One for Num {
+    fn one() -> Num {
+        Num::Small(1)
+    }
+}
+
+impl<'a> Sum<&'a Num> for Num {
+    fn sum<I: Iterator<Item = &'a Num>>(iter: I) -> Num {
+        iter.fold(Num::zero(), |total, i| total + i)
+    }
+}
+
+impl<'a> Product<&'a Num> for Num {
+    fn product<I: Iterator<Item = &'a Num>>(iter: I) -> Num {
+        iter.fold(Num::one(), |total, i| total * i)
+    }
+}
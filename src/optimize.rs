@@ -0,0 +1,203 @@
+use crate::neighbours::quietly;
+use crate::{execute_with_limit, BasicFunc, DoubleFunc, Func, HigherFunc, Object, OUT_OF_STEPS};
+
+use std::panic;
+
+// Steps allowed when pre-evaluating a constant subtree. Subtrees that need more are left alone.
+const FOLD_STEP_LIMIT: u64 = 10_000;
+
+// What is known about an object before the program runs.
+// Number and List also allow for an error in place of the object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    // An int or a rational.
+    Number,
+    List,
+    Unknown,
+}
+
+fn object_kind(object: &Object) -> Kind {
+    match object {
+        Object::Int(_) | Object::Rational(_) => Kind::Number,
+        Object::List(_) => Kind::List,
+        Object::Error(_) => Kind::Unknown,
+    }
+}
+
+fn output_kind(func: &Func, input: Kind) -> Kind {
+    use BasicFunc::*;
+    use Kind::*;
+    match func {
+        Func::Basic(basic) => match (basic, input) {
+            (Constant, _) => Number,
+            (Head | Tail | Negate | Sum | PowerSet, Number) => Number,
+            (Tail | Negate | PowerSet, List) => List,
+            (Length | Equal, List) => Number,
+            (Length | Equal | Product, Number) => List,
+            (Combine | AllPair | Deduplicate, _) => List,
+            _ => Unknown,
+        },
+        Func::Higher(higher_func, _) => match higher_func {
+            HigherFunc::Map
+            | HigherFunc::Filter
+            | HigherFunc::Order
+            | HigherFunc::FixedPoint
+            | HigherFunc::Repeat
+            | HigherFunc::GroupBy => List,
+            _ => Unknown,
+        },
+        Func::Double(_, _, _) => List,
+        Func::Bound(funcs) => funcs
+            .iter()
+            .rev()
+            .fold(input, |kind, func| output_kind(func, kind)),
+        Func::Literal(object) => object_kind(object),
+    }
+}
+
+// Whether func gives the same result on every input.
+fn is_constant(func: &Func) -> bool {
+    match func {
+        Func::Basic(BasicFunc::Constant) | Func::Literal(_) => true,
+        Func::Bound(funcs) => funcs.iter().any(is_constant),
+        Func::Double(DoubleFunc::Bifurcate, func1, func2) => {
+            is_constant(func1) && is_constant(func2)
+        }
+        _ => false,
+    }
+}
+
+// Runs a constant func ahead of time, unless it runs too long or panics.
+fn fold(func: &Func) -> Option<Func> {
+    let result = quietly(|| {
+        panic::catch_unwind(|| {
+            execute_with_limit(func, Object::Int(0.into()), Some(FOLD_STEP_LIMIT)).0
+        })
+    });
+    match result {
+        Ok(Object::Error(e)) if e == OUT_OF_STEPS => None,
+        Ok(object) => Some(Func::Literal(object)),
+        Err(_) => None,
+    }
+}
+
+fn is_basic(func: &Func, basic: BasicFunc) -> bool {
+    func == &Func::Basic(basic)
+}
+
+// The kind of the elements a higher-order function passes to its inner function.
+fn inner_kind(higher_func: &HigherFunc, input: Kind) -> Kind {
+    match higher_func {
+        HigherFunc::Map | HigherFunc::Filter | HigherFunc::Order | HigherFunc::GroupBy
+            if input == Kind::Number =>
+        {
+            Kind::Number
+        }
+        _ => Kind::Unknown,
+    }
+}
+
+// Rewrites a sequence of funcs, which run from last to first.
+fn optimize_bound(funcs: Vec<Func>, input: Kind, fold_constants: bool) -> Vec<Func> {
+    // Walk from the first func run to the last, flattening nested sequences,
+    // cancelling nn everywhere, and ht and th when they are applied to a number.
+    let mut out: Vec<Func> = vec![];
+    let mut kinds: Vec<Kind> = vec![input];
+    for func in funcs.into_iter().rev() {
+        let kind = *kinds.last().expect("Starts nonempty");
+        let pieces = match optimize_inner(func, kind, fold_constants) {
+            Func::Bound(inner) => inner,
+            other => vec![other],
+        };
+        for piece in pieces.into_iter().rev() {
+            let cancels = match out.last() {
+                Some(prev) if is_basic(prev, BasicFunc::Negate) => {
+                    is_basic(&piece, BasicFunc::Negate)
+                }
+                Some(prev) if kinds[kinds.len() - 2] == Kind::Number => {
+                    (is_basic(prev, BasicFunc::Head) && is_basic(&piece, BasicFunc::Tail))
+                        || (is_basic(prev, BasicFunc::Tail) && is_basic(&piece, BasicFunc::Head))
+                }
+                _ => false,
+            };
+            if cancels {
+                out.pop();
+                kinds.pop();
+            } else {
+                let kind = output_kind(&piece, *kinds.last().expect("Starts nonempty"));
+                out.push(piece);
+                kinds.push(kind);
+            }
+        }
+    }
+    out.reverse();
+    if fold_constants {
+        if let Some(last_constant) = out.iter().rposition(is_constant) {
+            // Nothing run before a constant can affect the result.
+            out.truncate(last_constant + 1);
+            let is_trivial = last_constant == 0
+                && matches!(out[0], Func::Basic(BasicFunc::Constant) | Func::Literal(_));
+            if !is_trivial {
+                if let Some(folded) = fold(&Func::Bound(out.clone())) {
+                    out = vec![folded];
+                }
+            }
+        }
+    }
+    out
+}
+
+fn optimize_inner(func: Func, input: Kind, fold_constants: bool) -> Func {
+    match func {
+        Func::Basic(_) | Func::Literal(_) => func,
+        Func::Higher(HigherFunc::Inverse, inner) => match *inner {
+            Func::Higher(HigherFunc::Inverse, twice_inner) => {
+                optimize_inner(*twice_inner, input, fold_constants)
+            }
+            // Constants are never folded beneath an inverse, which has to undo them.
+            other => match optimize_inner(other, Kind::Unknown, false) {
+                Func::Higher(HigherFunc::Inverse, twice_inner) => *twice_inner,
+                inner => Func::Higher(HigherFunc::Inverse, Box::new(inner)),
+            },
+        },
+        // Kept as is, so that the check still runs, and like any inverse not folded beneath.
+        Func::Higher(checked @ HigherFunc::CheckedInverse(_), inner) => Func::Higher(
+            checked,
+            Box::new(optimize_inner(*inner, Kind::Unknown, false)),
+        ),
+        Func::Higher(higher_func, inner) => {
+            let kind = inner_kind(&higher_func, input);
+            let inner = optimize_inner(*inner, kind, fold_constants);
+            Func::Higher(higher_func, Box::new(inner))
+        }
+        Func::Double(double_func, func1, func2) => {
+            let kind = match double_func {
+                DoubleFunc::Bifurcate => input,
+                DoubleFunc::While => Kind::Unknown,
+            };
+            Func::Double(
+                double_func,
+                Box::new(optimize_inner(*func1, kind, fold_constants)),
+                Box::new(optimize_inner(*func2, kind, fold_constants)),
+            )
+        }
+        Func::Bound(funcs) => {
+            let mut out = optimize_bound(funcs, input, fold_constants);
+            if out.len() == 1 {
+                out.pop().expect("Length 1")
+            } else {
+                Func::Bound(out)
+            }
+        }
+    }
+}
+
+// Rewrites func into an equivalent func that runs faster on input: nested groups are
+// flattened, operations that cancel are removed, and parts that don't depend on the
+// input are evaluated ahead of time.
+pub fn optimize(func: Func, input: &Object) -> Func {
+    match optimize_inner(func, object_kind(input), true) {
+        bound @ Func::Bound(_) => bound,
+        other => Func::Bound(vec![other]),
+    }
+}
@@ -0,0 +1,134 @@
+use crate::neighbours::{is_quiet, quietly};
+use crate::seq::Generator;
+use crate::{counting_steps, steps_remaining, take_steps, Callee, Object};
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic;
+use std::thread;
+
+// Lists shorter than this aren't worth starting threads for.
+const MIN_PER_THREAD: usize = 16;
+
+// The interpreter recurses deeply, so workers get the same room as the main thread.
+const STACK_SIZE: usize = 8 << 20;
+
+thread_local! {
+    // Workers start with 1, so work is only ever split across threads once.
+    static THREADS: Cell<usize> = const { Cell::new(1) };
+}
+
+// How many threads higher-order functions may split their work across.
+pub fn threads() -> usize {
+    THREADS.with(|t| t.get())
+}
+
+pub fn set_threads(count: usize) {
+    THREADS.with(|t| t.set(count.max(1)));
+}
+
+// Runs func on every item, returning the results in order. With more than one thread,
+// the items are split into contiguous chunks, one per thread. Results are left as lazy
+// as the sequential map leaves them. Each worker may take what's left of the step limit,
+// and the steps they all take are charged here once they finish, so together they can
+// overrun the limit before it's noticed. A panic in a worker is passed on.
+pub fn map<F: Callee>(func: &F, items: Vec<Object>, threads: usize) -> Vec<Object> {
+    let threads = threads.min(items.len() / MIN_PER_THREAD);
+    if threads <= 1 {
+        return items.into_iter().map(|obj| func.execute(obj)).collect();
+    }
+    let remaining = steps_remaining();
+    // Panics the caller is keeping quiet about stay quiet on the workers.
+    let quiet = is_quiet();
+    let chunk_size = items.len().div_ceil(threads);
+    let mut chunks = vec![];
+    let mut items = items.into_iter();
+    loop {
+        let chunk: Vec<Object> = items.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        chunks.push(chunk);
+    }
+    let finished: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                thread::Builder::new()
+                    .stack_size(STACK_SIZE)
+                    .spawn_scoped(scope, move || {
+                        let work = || {
+                            counting_steps(remaining, || {
+                                chunk
+                                    .into_iter()
+                                    .map(|obj| func.execute(obj))
+                                    .collect::<Vec<Object>>()
+                            })
+                        };
+                        if quiet {
+                            quietly(work)
+                        } else {
+                            work()
+                        }
+                    })
+                    .expect("Thread started")
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join()).collect()
+    });
+    let mut results = vec![];
+    for outcome in finished {
+        match outcome {
+            Ok((chunk_results, steps)) => {
+                take_steps(steps);
+                results.extend(chunk_results);
+            }
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+    results
+}
+
+// Keeps the items of source on which func is truthy, testing a batch of items at a time
+// across threads. Lazy, like the sequential filter, but may test a few items past the last
+// one needed.
+pub fn filter<F: Callee>(func: F, source: Generator, threads: usize) -> Generator {
+    Box::new(Filtered {
+        func,
+        source,
+        threads,
+        kept: VecDeque::new(),
+    })
+}
+
+struct Filtered<F> {
+    func: F,
+    source: Generator,
+    threads: usize,
+    kept: VecDeque<Object>,
+}
+
+impl<F: Callee> Iterator for Filtered<F> {
+    type Item = Object;
+    fn next(&mut self) -> Option<Object> {
+        while self.kept.is_empty() {
+            let batch: Vec<Object> = self
+                .source
+                .by_ref()
+                .take(self.threads * MIN_PER_THREAD)
+                .collect();
+            if batch.is_empty() {
+                return None;
+            }
+            let tests = map(&self.func, batch.clone(), self.threads);
+            self.kept.extend(
+                batch
+                    .into_iter()
+                    .zip(tests)
+                    .filter(|(_, test)| test.is_truthy())
+                    .map(|(obj, _)| obj),
+            );
+        }
+        self.kept.pop_front()
+    }
+}
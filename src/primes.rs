@@ -0,0 +1,229 @@
+use crate::number::Num;
+use crate::take_step;
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+// Miller-Rabin with these bases is exact below EXACT_LIMIT.
+const BASES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+// The smallest strong pseudoprime to all of BASES, found by Sorenson and Webster.
+// No larger set of bases has been proven exact over a wider range.
+const EXACT_LIMIT: &str = "3317044064679887385961981";
+
+// Small factors are cheaper to find by trial division than with Pollard's rho.
+const TRIAL_LIMIT: u32 = 1000;
+
+// Whether n is prime. Below EXACT_LIMIT this is Miller-Rabin with every one of BASES,
+// which is exact. From there on it's Baillie-PSW: Miller-Rabin with base 2, then a strong
+// Lucas test. No composite is known to pass both, but that isn't proven,
+// so true above EXACT_LIMIT only means n is a probable prime.
+// Costs a step per base tested, and one for the Lucas test.
+// None if the step limit runs out first.
+pub fn is_prime(n: &Num) -> Option<bool> {
+    let n = n.to_big();
+    if n < BigInt::from(2) {
+        return Some(false);
+    }
+    for base in BASES {
+        if n == BigInt::from(base) {
+            return Some(true);
+        }
+        if (&n % base).is_zero() {
+            return Some(false);
+        }
+    }
+    let exact = n < EXACT_LIMIT.parse().expect("Valid limit");
+    let bases = if exact { &BASES[..] } else { &BASES[..1] };
+    for &base in bases {
+        if !take_step() {
+            return None;
+        }
+        if !is_strong_probable_prime(&n, base) {
+            return Some(false);
+        }
+    }
+    if exact {
+        return Some(true);
+    }
+    if !take_step() {
+        return None;
+    }
+    Some(is_strong_lucas_probable_prime(&n))
+}
+
+// Miller-Rabin with a single base, for odd n above the base.
+fn is_strong_probable_prime(n: &BigInt, base: u32) -> bool {
+    let minus_one: BigInt = n - 1;
+    let twos = minus_one.trailing_zeros().expect("Nonzero");
+    let odd = &minus_one >> twos;
+    let mut x = BigInt::from(base).modpow(&odd, n);
+    if x.is_one() || x == minus_one {
+        return true;
+    }
+    for _ in 1..twos {
+        x = &x * &x % n;
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+// The strong Lucas test with Selfridge's parameters, for odd n with no small factors:
+// D is the first of 5, -7, 9, -11, ... with Jacobi symbol (D/n) = -1, P = 1
+// and Q = (1 - D) / 4.
+fn is_strong_lucas_probable_prime(n: &BigInt) -> bool {
+    // No D is found for a square, so squares are ruled out first.
+    let root = n.sqrt();
+    if &(&root * &root) == n {
+        return false;
+    }
+    let mut d = BigInt::from(5);
+    loop {
+        match jacobi(&d, n) {
+            -1 => break,
+            0 if &d.abs() != n => return false,
+            _ => {}
+        }
+        let two = BigInt::from(2);
+        d = if d.is_positive() {
+            -(d + two)
+        } else {
+            -(d - two)
+        };
+    }
+    let q: BigInt = (1 - &d) / 4;
+    let reduce = |x: BigInt| ((x % n) + n) % n;
+    let halve = |x: BigInt| {
+        let x = if x.bit(0) { x + n } else { x };
+        reduce(x / 2)
+    };
+    let plus_one: BigInt = n + 1;
+    let twos = plus_one.trailing_zeros().expect("Nonzero");
+    let odd = &plus_one >> twos;
+    // U_k, V_k and Q^k for k the bits of odd read so far, starting from k = 1.
+    let mut u = BigInt::one();
+    let mut v = BigInt::one();
+    let mut q_k = reduce(q.clone());
+    for bit in (0..odd.bits() - 1).rev() {
+        u = reduce(&u * &v);
+        v = reduce(&v * &v - 2 * &q_k);
+        q_k = reduce(&q_k * &q_k);
+        if odd.bit(bit) {
+            let next_u = halve(&u + &v);
+            v = halve(&d * &u + &v);
+            u = next_u;
+            q_k = reduce(&q_k * &q);
+        }
+    }
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..twos {
+        v = reduce(&v * &v - 2 * &q_k);
+        if v.is_zero() {
+            return true;
+        }
+        q_k = reduce(&q_k * &q_k);
+    }
+    false
+}
+
+// The Jacobi symbol (a/n), for odd positive n.
+fn jacobi(a: &BigInt, n: &BigInt) -> i32 {
+    let mut a = ((a % n) + n) % n;
+    let mut n = n.clone();
+    let mut result = 1;
+    while !a.is_zero() {
+        let twos = a.trailing_zeros().expect("Nonzero");
+        a >>= twos;
+        let n_mod_8 = (&n % 8u32).to_u32().expect("Small");
+        if twos % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            result = -result;
+        }
+        if (&a % 4u32).to_u32() == Some(3) && n_mod_8 % 4 == 3 {
+            result = -result;
+        }
+        std::mem::swap(&mut a, &mut n);
+        a %= &n;
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
+}
+
+// The prime factors of the absolute value of n, smallest first, with repeats.
+// Costs a step per trial division and per round of Pollard's rho.
+// None if the step limit runs out first.
+pub fn factorize(n: &Num) -> Option<Vec<Num>> {
+    let mut work = n.abs().to_big();
+    let mut factors: Vec<BigInt> = vec![];
+    if work < BigInt::from(2) {
+        return Some(vec![]);
+    }
+    let mut divisor = 2;
+    while divisor < TRIAL_LIMIT && BigInt::from(divisor * divisor) <= work {
+        if !take_step() {
+            return None;
+        }
+        if (&work % divisor).is_zero() {
+            work /= divisor;
+            factors.push(BigInt::from(divisor));
+        } else {
+            divisor += 1;
+        }
+    }
+    let mut unsplit = vec![];
+    if !work.is_one() {
+        unsplit.push(work);
+    }
+    while let Some(composite) = unsplit.pop() {
+        if is_prime(&Num::from(composite.clone()))? {
+            factors.push(composite);
+        } else {
+            let factor = rho(&composite)?;
+            unsplit.push(&composite / &factor);
+            unsplit.push(factor);
+        }
+    }
+    factors.sort();
+    Some(factors.into_iter().map(Num::from).collect())
+}
+
+// A nontrivial factor of the composite n, by Pollard's rho with Floyd's cycle finding,
+// trying x^2 + c for c = 1, 2, ... until one splits n.
+fn rho(n: &BigInt) -> Option<BigInt> {
+    let step = |x: &BigInt, c: &BigInt| (x * x + c) % n;
+    let mut c = BigInt::one();
+    loop {
+        let mut slow = BigInt::from(2);
+        let mut fast = BigInt::from(2);
+        loop {
+            if !take_step() {
+                return None;
+            }
+            slow = step(&slow, &c);
+            fast = step(&step(&fast, &c), &c);
+            let d = gcd((&slow - &fast).abs(), n.clone());
+            if &d == n {
+                break;
+            }
+            if !d.is_one() {
+                return Some(d);
+            }
+        }
+        c += 1;
+    }
+}
+
+fn gcd(mut a: BigInt, mut b: BigInt) -> BigInt {
+    while !b.is_zero() {
+        let r = &a % &b;
+        a = b;
+        b = r;
+    }
+    a
+}
@@ -0,0 +1,234 @@
+use crate::enumerate::objects;
+use crate::neighbours::quietly;
+use crate::number::Num;
+use crate::{counting_steps, out_of_steps, steps_remaining, take_step, take_steps, Callee, Object};
+
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+use std::panic::{self, AssertUnwindSafe};
+
+// The most steps a search may take, so that it gives up in reasonable time.
+const SEARCH_STEPS: u64 = 100_000;
+
+// Reverse lookup is asked for outright, so it may search for longer,
+// taking this many steps on each of ints, rationals and lists.
+const LOOKUP_STEPS: u64 = 10_000_000;
+
+// How many steps reverse lookup spends on each kind before the turns double.
+const FIRST_LOOKUP_TURN: u64 = 1000;
+
+// Reverse lookup tries lists nested at most this deep.
+const MAX_LOOKUP_DEPTH: usize = 3;
+
+// Ints whose divisors are worth trying first, since they're cheap to find.
+const MAX_DIVIDED: i64 = 1_000_000;
+
+// At most this many ints, and this many lists, are taken from a target to try first.
+const MAX_HINTS: usize = 64;
+
+// Lists up to this long have their slices tried first, as well as themselves.
+const MAX_SLICED: usize = 12;
+
+// Objects likely to make up a preimage of target: the ints in it, the numerators and
+// denominators of the rationals in it and their divisors, smallest first, then the
+// rationals in it, then the lists in it and their slices, shortest first.
+fn hints(target: &Object) -> Vec<Object> {
+    let mut found_ints = vec![];
+    let mut found_rationals = vec![];
+    let mut found_lists = vec![];
+    add_parts(
+        target,
+        &mut found_ints,
+        &mut found_rationals,
+        &mut found_lists,
+    );
+    let mut ints = vec![];
+    for n in found_ints {
+        match n.abs().to_big().to_i64() {
+            Some(n) if n <= MAX_DIVIDED => {
+                ints.extend(
+                    (1..)
+                        .take_while(|d| d * d <= n)
+                        .filter(|d| n % d == 0)
+                        .flat_map(|d| [Num::from(d), Num::from(n / d)]),
+                );
+            }
+            _ => ints.push(n.abs()),
+        }
+        if n.is_negative() {
+            ints.push(n);
+        }
+    }
+    ints.sort_by_key(|n| (n.abs(), n.is_negative()));
+    ints.dedup();
+    let mut lists = vec![];
+    for elems in found_lists {
+        if elems.len() <= MAX_SLICED {
+            for start in 0..=elems.len() {
+                for end in start..=elems.len() {
+                    lists.push(elems[start..end].to_vec());
+                }
+            }
+        } else {
+            lists.push(elems);
+        }
+    }
+    lists.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    lists.dedup();
+    found_rationals.sort();
+    found_rationals.dedup();
+    let ints = ints.into_iter().map(Object::Int);
+    let rationals = found_rationals.into_iter().map(Object::Rational);
+    let lists = lists.into_iter().map(|elems| Object::List(elems.into()));
+    ints.chain(rationals).chain(lists).collect()
+}
+
+// The ints, rationals and lists already made in obj, up to MAX_HINTS of each.
+// A rational's numerator and denominator count as ints in it.
+fn add_parts(
+    obj: &Object,
+    ints: &mut Vec<Num>,
+    rationals: &mut Vec<BigRational>,
+    lists: &mut Vec<Vec<Object>>,
+) {
+    match obj {
+        Object::Int(n) if ints.len() < MAX_HINTS => ints.push(n.clone()),
+        Object::Rational(r) if rationals.len() < MAX_HINTS => {
+            rationals.push(r.clone());
+            for part in [r.numer(), r.denom()] {
+                if ints.len() < MAX_HINTS {
+                    ints.push(Num::from(part.clone()));
+                }
+            }
+        }
+        Object::List(l) if lists.len() < MAX_HINTS => {
+            let mut elems = vec![];
+            l.for_each_made(|elem| elems.push(elem.clone()));
+            for elem in &elems {
+                add_parts(elem, ints, rationals, lists);
+            }
+            lists.push(elems);
+        }
+        _ => {}
+    }
+}
+
+// The smallest object which func maps to target, or an error if none is found in time.
+pub fn preimage<F: Callee>(func: &F, target: &Object) -> Object {
+    let candidates = objects().hints(hints(target));
+    Preimages::new(
+        func.clone(),
+        target.clone(),
+        Box::new(candidates),
+        SEARCH_STEPS,
+    )
+    .search()
+    .unwrap_or_else(|| Object::Error("No preimage found within the step limit".to_string()))
+}
+
+// Every object which func maps to target, smallest first, as they're found.
+// Making the candidates isn't counted against each search, only testing them,
+// so this runs until stopped if there are infinitely many preimages.
+pub fn preimages<F: Callee>(func: F, target: Object) -> impl Iterator<Item = Object> {
+    let candidates = objects().hints(hints(&target));
+    let mut preimages = Preimages::new(func, target, Box::new(candidates), SEARCH_STEPS);
+    preimages.free_candidates = true;
+    preimages
+}
+
+// The first input which func maps to target. Ints, in the order 0, 1, -1, 2, ...,
+// rationals, and lists nested at most MAX_LOOKUP_DEPTH deep, smallest first, are searched
+// in turns, each twice as long as the last, so that ints come up about as quickly as they
+// used to and lists of any length are reached. Each search resumes where its last turn
+// stopped.
+pub fn lookup<F: Callee>(func: &F, target: &Object) -> Object {
+    let ints: Candidates = Box::new(objects().ints_only());
+    let rationals: Candidates = Box::new(objects().rationals_only());
+    let lists: Candidates = Box::new(objects().lists_only().max_depth(MAX_LOOKUP_DEPTH));
+    let mut searches = [ints, rationals, lists]
+        .map(|candidates| Preimages::new(func.clone(), target.clone(), candidates, 0));
+    let mut spent = 0;
+    let mut turn = FIRST_LOOKUP_TURN;
+    while spent < LOOKUP_STEPS && !out_of_steps() {
+        let turn_steps = turn.min(LOOKUP_STEPS - spent);
+        for search in &mut searches {
+            search.budget = turn_steps;
+            if let Some(found) = search.search() {
+                return found;
+            }
+        }
+        spent += turn_steps;
+        turn *= 2;
+    }
+    Object::Error("No input found within the step limit".to_string())
+}
+
+type Candidates = Box<dyn Iterator<Item = Object> + Send>;
+
+struct Preimages<F> {
+    func: F,
+    target: Object,
+    candidates: Candidates,
+    // How many steps each search may take.
+    budget: u64,
+    // A candidate whose test ran out of steps, to be tested again.
+    retry: Option<Object>,
+    // Whether making candidates is left out of the steps each search takes.
+    free_candidates: bool,
+}
+
+impl<F: Callee> Preimages<F> {
+    fn new(func: F, target: Object, candidates: Candidates, budget: u64) -> Preimages<F> {
+        Preimages {
+            func,
+            target,
+            candidates,
+            budget,
+            retry: None,
+            free_candidates: false,
+        }
+    }
+    fn next_candidate(&mut self) -> Option<Object> {
+        if self.free_candidates {
+            counting_steps(None, || self.candidates.next()).0
+        } else {
+            self.candidates.next()
+        }
+    }
+    // The next preimage, trying each candidate in turn. Candidates which make func panic
+    // are skipped. Searches for the budget, or until the step limit if that comes sooner,
+    // and the steps taken are charged here.
+    fn search(&mut self) -> Option<Object> {
+        let budget = steps_remaining().map_or(self.budget, |r| r.min(self.budget));
+        let (found, steps) = counting_steps(Some(budget), || {
+            quietly(|| loop {
+                let candidate = self.retry.take().or_else(|| self.next_candidate())?;
+                let output = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let output = self.func.execute(candidate.clone());
+                    output.force();
+                    output
+                }));
+                if out_of_steps() {
+                    self.retry = Some(candidate);
+                    return None;
+                }
+                if matches!(output, Ok(output) if output == self.target) {
+                    return Some(candidate);
+                }
+            })
+        });
+        take_steps(steps);
+        found
+    }
+}
+
+impl<F: Callee> Iterator for Preimages<F> {
+    type Item = Object;
+    fn next(&mut self) -> Option<Object> {
+        if !take_step() {
+            return None;
+        }
+        self.search()
+    }
+}
@@ -0,0 +1,498 @@
+use crate::number::Num;
+use crate::{out_of_steps, take_step, Object, OUT_OF_STEPS};
+
+use num_traits::cast::ToPrimitive;
+use num_traits::{One, Zero};
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::panic::RefUnwindSafe;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Shared, immutable list storage. Cloning a list or taking its tail is O(1),
+// and elements are only copied when a shared list is modified.
+#[derive(Clone)]
+pub struct Seq(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Items {
+        items: Arc<Vec<Object>>,
+        start: usize,
+        end: usize,
+    },
+    // The ints start, start + step, ... with len elements in all, where step is 1 or -1.
+    // The elements are only built once the list is read as a slice.
+    Range {
+        start: Num,
+        len: Num,
+        step: i64,
+        built: Arc<OnceLock<Vec<Object>>>,
+    },
+    // Elements made one at a time by a generator, and kept once made.
+    Lazy(Arc<Lazy>),
+}
+
+pub type Generator = Box<dyn Iterator<Item = Object> + Send>;
+
+// Computes the element at an index directly.
+pub type Unrank = Arc<dyn Fn(&Num) -> Object + Send + Sync + RefUnwindSafe>;
+
+struct Lazy {
+    len: OnceLock<Num>,
+    made: Mutex<Made>,
+    built: OnceLock<Vec<Object>>,
+    // Present when any element can be found without making the ones before it.
+    unrank: Option<Unrank>,
+}
+
+struct Made {
+    items: Vec<Object>,
+    rest: Generator,
+}
+
+impl Lazy {
+    fn get(&self, index: usize) -> Option<Object> {
+        if let Some(built) = self.built.get() {
+            return built.get(index).cloned();
+        }
+        let mut made = self.made.lock().expect("Generator panicked");
+        while made.items.len() <= index {
+            let next = made.rest.next()?;
+            made.items.push(next);
+        }
+        Some(made.items[index].clone())
+    }
+    fn nth(&self, index: &Num) -> Option<Object> {
+        match (&self.unrank, self.built.get()) {
+            (Some(unrank), None) => {
+                let len = self.len.get().expect("Unrankable lists know their length");
+                if !index.is_negative() && index < len && take_step() {
+                    Some(unrank(index))
+                } else {
+                    None
+                }
+            }
+            _ => self.get(index.to_usize()?),
+        }
+    }
+    // Every element, or none if the generator runs out of steps first, in which case
+    // the func reading it gives the step limit error instead of what it made.
+    // The elements made so far are kept, but only a finished list is stored as built.
+    fn build(&self) -> &[Object] {
+        if let Some(built) = self.built.get() {
+            return built;
+        }
+        let mut made = self.made.lock().expect("Generator panicked");
+        let Made { items, rest } = &mut *made;
+        items.extend(rest);
+        if out_of_steps() {
+            return &[];
+        }
+        let items = std::mem::take(items);
+        self.built.get_or_init(|| items)
+    }
+    // Like build, but gives the elements made so far if the generator runs out of steps.
+    fn to_vec(&self) -> Vec<Object> {
+        let built = self.build();
+        if self.built.get().is_some() {
+            built.to_vec()
+        } else {
+            self.made.lock().expect("Generator panicked").items.clone()
+        }
+    }
+    fn count(&self) -> Num {
+        if let Some(len) = self.len.get() {
+            return len.clone();
+        }
+        let built = self.build();
+        if self.built.get().is_some() {
+            self.len.get_or_init(|| Num::from(built.len())).clone()
+        } else {
+            Num::from(built.len())
+        }
+    }
+}
+
+// Yields the elements of seq from index on, making lazy elements only as they are reached.
+// Stops once out of steps, so that a filter over a long range doesn't scan on.
+struct SeqIter {
+    seq: Seq,
+    index: usize,
+}
+
+impl Iterator for SeqIter {
+    type Item = Object;
+    fn next(&mut self) -> Option<Object> {
+        if out_of_steps() {
+            return None;
+        }
+        let next = self.seq.get(self.index)?;
+        self.index += 1;
+        Some(next)
+    }
+}
+
+// Yields unrank of each index below len, a step at a time.
+struct UnrankIter {
+    index: Num,
+    len: Num,
+    unrank: Unrank,
+}
+
+impl Iterator for UnrankIter {
+    type Item = Object;
+    fn next(&mut self) -> Option<Object> {
+        if self.index >= self.len || !take_step() {
+            return None;
+        }
+        let next = (self.unrank)(&self.index);
+        self.index += 1;
+        Some(next)
+    }
+}
+
+impl Seq {
+    pub fn range(start: Num, len: Num, step: i64) -> Seq {
+        if len.is_zero() {
+            return Seq::default();
+        }
+        Seq(Repr::Range {
+            start,
+            len,
+            step,
+            built: Arc::default(),
+        })
+    }
+    // A list whose elements come from rest as needed, with len elements if known.
+    // rest should take a step per element, and stop early when out of steps.
+    pub fn lazy(len: Option<Num>, rest: Generator) -> Seq {
+        if len.as_ref().is_some_and(|len| len.is_zero()) {
+            return Seq::default();
+        }
+        let known = OnceLock::new();
+        if let Some(len) = len {
+            known.set(len).expect("Fresh");
+        }
+        Seq(Repr::Lazy(Arc::new(Lazy {
+            len: known,
+            made: Mutex::new(Made {
+                items: vec![],
+                rest,
+            }),
+            built: OnceLock::new(),
+            unrank: None,
+        })))
+    }
+    // A list of len elements, where unrank gives the element at each index.
+    pub fn indexed(len: Num, unrank: Unrank) -> Seq {
+        if len.is_zero() {
+            return Seq::default();
+        }
+        let rest = Box::new(UnrankIter {
+            index: Zero::zero(),
+            len: len.clone(),
+            unrank: unrank.clone(),
+        });
+        Seq(Repr::Lazy(Arc::new(Lazy {
+            len: OnceLock::from(len),
+            made: Mutex::new(Made {
+                items: vec![],
+                rest,
+            }),
+            built: OnceLock::new(),
+            unrank: Some(unrank),
+        })))
+    }
+    // Visits the elements held in memory, without making or copying any.
+    // Unbuilt ranges hold none, and neither does a lazy list whose generator is running.
+    pub fn for_each_made(&self, mut visit: impl FnMut(&Object)) {
+        match &self.0 {
+            Repr::Items { items, start, end } => items[*start..*end].iter().for_each(visit),
+            Repr::Range { built, .. } => built.get().into_iter().flatten().for_each(visit),
+            Repr::Lazy(lazy) => match lazy.built.get() {
+                Some(built) => built.iter().for_each(visit),
+                None => {
+                    if let Ok(made) = lazy.made.try_lock() {
+                        made.items.iter().for_each(&mut visit)
+                    }
+                }
+            },
+        }
+    }
+    // The elements from index on, without making lazy elements before they are needed.
+    pub fn iter_from(&self, index: usize) -> Generator {
+        Box::new(SeqIter {
+            seq: self.clone(),
+            index,
+        })
+    }
+    // [0, i) for nonnegative i, and its reverse [-i-1, ..., 0] for negative i.
+    pub fn upto(i: Num) -> Seq {
+        if i.is_negative() {
+            Seq::range(-&i - 1, -i, -1)
+        } else {
+            Seq::range(Zero::zero(), i, 1)
+        }
+    }
+    // The length as an unbounded int, which a range may need.
+    pub fn count(&self) -> Num {
+        match &self.0 {
+            Repr::Items { start, end, .. } => Num::from(end - start),
+            Repr::Range { len, .. } => len.clone(),
+            Repr::Lazy(lazy) => lazy.count(),
+        }
+    }
+    // Shadows the slice method, so that ranges need not be built.
+    // None if the list is too long to hold in memory, which count still gives.
+    pub fn len(&self) -> Option<usize> {
+        match &self.0 {
+            Repr::Items { start, end, .. } => Some(end - start),
+            Repr::Range { len, .. } => len.to_usize(),
+            Repr::Lazy(lazy) => lazy.count().to_usize(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        match &self.0 {
+            Repr::Items { start, end, .. } => start == end,
+            Repr::Range { .. } => false,
+            Repr::Lazy(lazy) => lazy.get(0).is_none(),
+        }
+    }
+    // The element at index, or None past the end.
+    pub fn get(&self, index: usize) -> Option<Object> {
+        match &self.0 {
+            Repr::Items { .. } => (**self).get(index).cloned(),
+            Repr::Range { len, .. } => {
+                let index = Num::from(index);
+                if &index < len {
+                    Some(self.nth(&index))
+                } else {
+                    None
+                }
+            }
+            Repr::Lazy(lazy) => lazy.nth(&Num::from(index)),
+        }
+    }
+    // The element at index, which must be in bounds.
+    // A lazy list that runs out of steps before reaching it gives an error.
+    pub fn nth(&self, index: &Num) -> Object {
+        match &self.0 {
+            Repr::Items { .. } => self[index.to_usize().expect("Index in bounds")].clone(),
+            Repr::Range { start, step, .. } => Object::Int(start + index * *step),
+            Repr::Lazy(lazy) => lazy
+                .nth(index)
+                .unwrap_or_else(|| Object::Error(OUT_OF_STEPS.to_string())),
+        }
+    }
+    pub fn front(&self) -> Option<Object> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.nth(&Zero::zero()))
+        }
+    }
+    pub fn back(&self) -> Option<Object> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.nth(&(self.count() - 1)))
+        }
+    }
+    // All but the first element.
+    pub fn tail(&self) -> Seq {
+        assert!(!self.is_empty(), "Tail of empty seq");
+        match &self.0 {
+            Repr::Items { items, start, end } => Seq(Repr::Items {
+                items: items.clone(),
+                start: start + 1,
+                end: *end,
+            }),
+            Repr::Range {
+                start, len, step, ..
+            } => Seq::range(start + *step, len - 1, *step),
+            Repr::Lazy(lazy) => match &lazy.unrank {
+                Some(unrank) => {
+                    let unrank = unrank.clone();
+                    let shifted = move |index: &Num| unrank(&(index + 1));
+                    Seq::indexed(lazy.count() - 1, Arc::new(shifted))
+                }
+                None => Seq::lazy(lazy.len.get().map(|len| len - 1), self.iter_from(1)),
+            },
+        }
+    }
+    // All but the last element.
+    pub fn init(&self) -> Seq {
+        assert!(!self.is_empty(), "Init of empty seq");
+        match &self.0 {
+            Repr::Items { items, start, end } => Seq(Repr::Items {
+                items: items.clone(),
+                start: *start,
+                end: end - 1,
+            }),
+            Repr::Range {
+                start, len, step, ..
+            } => Seq::range(start.clone(), len - 1, *step),
+            Repr::Lazy(lazy) => match &lazy.unrank {
+                Some(unrank) => Seq::indexed(lazy.count() - 1, unrank.clone()),
+                None => Seq::from(self.to_vec()).init(),
+            },
+        }
+    }
+    pub fn reversed(self) -> Seq {
+        match &self.0 {
+            Repr::Items { .. } | Repr::Lazy(_) => {
+                let mut items = self.into_vec();
+                items.reverse();
+                Seq::from(items)
+            }
+            Repr::Range {
+                start, len, step, ..
+            } => Seq::range(start + (len - 1) * *step, len.clone(), -step),
+        }
+    }
+    // The sum of a range, found without building it.
+    pub fn range_sum(&self) -> Option<Num> {
+        match &self.0 {
+            Repr::Items { .. } | Repr::Lazy(_) => None,
+            Repr::Range {
+                start, len, step, ..
+            } => {
+                let last = start + (len - 1) * *step;
+                Some(len * (start + last) / 2)
+            }
+        }
+    }
+    // Applies x -> sign * x + offset to every element of a range, without building it.
+    pub fn map_affine(&self, sign: i64, offset: &Num) -> Option<Seq> {
+        match &self.0 {
+            Repr::Items { .. } | Repr::Lazy(_) => None,
+            Repr::Range {
+                start, len, step, ..
+            } => Some(Seq::range(start * sign + offset, len.clone(), step * sign)),
+        }
+    }
+    pub fn into_vec(self) -> Vec<Object> {
+        match self.0 {
+            Repr::Items { items, start, end } if start == 0 && end == items.len() => {
+                Arc::try_unwrap(items).unwrap_or_else(|items| items.to_vec())
+            }
+            Repr::Lazy(lazy) => lazy.to_vec(),
+            _ => self.to_vec(),
+        }
+    }
+}
+
+// Builds the elements of a range, a step at a time, or None if it runs out of steps.
+fn build_range(start: &Num, len: &Num, step: i64) -> Option<Vec<Object>> {
+    let mut nums = vec![];
+    let mut current = start.clone();
+    let mut index: Num = Zero::zero();
+    while &index < len {
+        if !take_step() {
+            return None;
+        }
+        nums.push(Object::Int(current.clone()));
+        current += step;
+        index += 1;
+    }
+    Some(nums)
+}
+
+impl Default for Seq {
+    fn default() -> Seq {
+        Seq::from(vec![])
+    }
+}
+
+// A list cut short by the step limit reads as empty,
+// and so compares and hashes as empty, until the func reading it gives the error.
+impl Deref for Seq {
+    type Target = [Object];
+    fn deref(&self) -> &[Object] {
+        match &self.0 {
+            Repr::Items { items, start, end } => &items[*start..*end],
+            Repr::Range {
+                start,
+                len,
+                step,
+                built,
+            } => match built.get() {
+                Some(items) => items,
+                // Nothing is kept from a build cut short, so that it's made in full later.
+                None => match build_range(start, len, *step) {
+                    Some(items) => built.get_or_init(|| items),
+                    None => &[],
+                },
+            },
+            Repr::Lazy(lazy) => lazy.build(),
+        }
+    }
+}
+
+impl From<Vec<Object>> for Seq {
+    fn from(items: Vec<Object>) -> Seq {
+        let end = items.len();
+        Seq(Repr::Items {
+            items: Arc::new(items),
+            start: 0,
+            end,
+        })
+    }
+}
+
+impl FromIterator<Object> for Seq {
+    fn from_iter<I: IntoIterator<Item = Object>>(iter: I) -> Seq {
+        Seq::from(iter.into_iter().collect::<Vec<Object>>())
+    }
+}
+
+impl IntoIterator for Seq {
+    type Item = Object;
+    type IntoIter = std::vec::IntoIter<Object>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Seq {
+    type Item = &'a Object;
+    type IntoIter = std::slice::Iter<'a, Object>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PartialEq for Seq {
+    fn eq(&self, other: &Seq) -> bool {
+        match (&self.0, &other.0) {
+            (
+                Repr::Range {
+                    start, len, step, ..
+                },
+                Repr::Range {
+                    start: other_start,
+                    len: other_len,
+                    step: other_step,
+                    ..
+                },
+            ) => len == other_len && start == other_start && (len.is_one() || step == other_step),
+            _ => **self == **other,
+        }
+    }
+}
+
+impl Eq for Seq {}
+
+impl Hash for Seq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl fmt::Debug for Seq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
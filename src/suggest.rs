@@ -0,0 +1,170 @@
+use crate::neighbours::{evaluate, quietly, single_edits, Edit, Mode, Outcome, EDIT_STEP_LIMIT};
+use crate::{Object, ParseError};
+
+use std::collections::HashSet;
+
+// At most this many suggestions are shown.
+const SUGGESTION_COUNT: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub program: String,
+    pub edit: Edit,
+    pub output: Object,
+    pub matches_expected: bool,
+}
+
+fn contains_error(object: &Object) -> bool {
+    match object {
+        Object::Int(_) | Object::Rational(_) => false,
+        Object::List(l) => l.iter().any(contains_error),
+        Object::Error(_) => true,
+    }
+}
+
+fn insert_everywhere(program: &str, new: char, from: usize) -> Vec<Edit> {
+    (from..=program.chars().count())
+        .map(|index| Edit::Insert(index, new))
+        .collect()
+}
+
+fn delete_each(program: &str, old: char) -> Vec<Edit> {
+    program
+        .chars()
+        .enumerate()
+        .filter(|&(_, c)| c == old)
+        .map(|(index, c)| Edit::Delete(index, c))
+        .collect()
+}
+
+fn swap_head_tail(program: &str) -> Vec<Edit> {
+    program
+        .chars()
+        .enumerate()
+        .filter_map(|(index, c)| match c {
+            'h' => Some(Edit::Substitute(index, 'h', 't')),
+            't' => Some(Edit::Substitute(index, 't', 'h')),
+            _ => None,
+        })
+        .collect()
+}
+
+// The edits most likely to fix failure, which are tried and ranked before all
+// other single edits.
+fn targeted_edits(program: &str, failure: &Outcome) -> Vec<Edit> {
+    match failure {
+        Outcome::ParseError(ParseError::UnknownChar(index, c)) => vec![Edit::Delete(*index, *c)],
+        Outcome::ParseError(ParseError::BindReachedFront) => {
+            let mut edits = delete_each(program, 'z');
+            edits.extend(
+                program
+                    .chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == 'z')
+                    .map(|(index, _)| Edit::Substitute(index, 'z', 'q')),
+            );
+            edits
+        }
+        Outcome::ParseError(ParseError::BindReachedQuote | ParseError::SoloQuoteUnbound) => {
+            let mut edits = insert_everywhere(program, 'q', 0);
+            edits.extend(delete_each(program, 'q'));
+            edits.extend(delete_each(program, 'z'));
+            edits
+        }
+        Outcome::RuntimeError(e) if e.ends_with("of empty list") => {
+            let mut edits = swap_head_tail(program);
+            edits.extend(insert_everywhere(program, 'z', 1));
+            edits
+        }
+        Outcome::RuntimeError(_) => {
+            let mut edits = insert_everywhere(program, 'z', 1);
+            edits.extend(swap_head_tail(program));
+            edits
+        }
+        Outcome::Output(_) => vec![],
+    }
+}
+
+// Single edits of a failing program that parse and run without error, run in the same
+// mode as it. Edits reaching expected come first, then edits targeted at the failure.
+pub fn suggestions(
+    program: &str,
+    input: &Object,
+    failure: &Outcome,
+    expected: Option<&Object>,
+    mode: Mode,
+) -> Vec<Suggestion> {
+    let targeted: Vec<(String, Edit)> = targeted_edits(program, failure)
+        .into_iter()
+        .map(|edit| (edit.apply(program), edit))
+        .collect();
+    let mut seen = HashSet::new();
+    let candidates: Vec<(String, Edit, bool)> = targeted
+        .into_iter()
+        .map(|(edited, edit)| (edited, edit, true))
+        .chain(
+            single_edits(program)
+                .into_iter()
+                .map(|(edited, edit)| (edited, edit, false)),
+        )
+        .filter(|(edited, _, _)| seen.insert(edited.clone()))
+        .collect();
+    let mut found: Vec<(Suggestion, bool)> = quietly(|| {
+        candidates
+            .into_iter()
+            .filter_map(|(edited, edit, targeted)| {
+                match evaluate(&edited, input, EDIT_STEP_LIMIT, mode) {
+                    Outcome::Output(output) if !contains_error(&output) => {
+                        let matches_expected = expected == Some(&output);
+                        let suggestion = Suggestion {
+                            program: edited,
+                            edit,
+                            output,
+                            matches_expected,
+                        };
+                        Some((suggestion, targeted))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    });
+    found.sort_by_key(|(suggestion, targeted)| (!suggestion.matches_expected, !targeted));
+    found
+        .into_iter()
+        .map(|(suggestion, _)| suggestion)
+        .take(SUGGESTION_COUNT)
+        .collect()
+}
+
+// The suggestions for a failing program, formatted for display after its output.
+pub fn did_you_mean(
+    program: &str,
+    input: &Object,
+    failure: &Outcome,
+    expected: Option<&Object>,
+    mode: Mode,
+) -> String {
+    let found = suggestions(program, input, failure, expected, mode);
+    if found.is_empty() {
+        return "No single edit runs without error".to_string();
+    }
+    let width = program.chars().count() + 1;
+    let mut lines = vec!["Did you mean:".to_string()];
+    for suggestion in found {
+        let marker = if suggestion.matches_expected {
+            " (expected output)"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "    {:width$} {:12} => {}{}",
+            suggestion.program,
+            format!("({})", suggestion.edit),
+            suggestion.output,
+            marker,
+            width = width
+        ));
+    }
+    lines.join("\n")
+}